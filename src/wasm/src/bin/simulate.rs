@@ -0,0 +1,105 @@
+//! Headless race runner.
+//!
+//! Runs the game stage with a scripted input file and no browser, then prints
+//! the outcome of the race.
+//!
+//! ```sh
+//! $ cargo run --bin simulate -- race.txt [--record race.replay] [--max-ticks 36000]
+//! $ cargo run --bin simulate -- --replay race.replay
+//! ```
+use anyhow::{Result, anyhow};
+use std::{cell::RefCell, process::ExitCode, rc::Rc};
+use wasm::engine::{ActionState, Game, Input, LoadProgress};
+use wasm::game::{GameStage, Replay};
+use wasm::headless::{FileAssets, MemoryKeyBindings, ScriptedInput, SilentAudio};
+
+/// Directory served as the web root, holding the track files
+const WEB_ROOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../..");
+
+/// Ten minutes at 60 ticks per second
+const DEFAULT_MAX_TICKS: u32 = 36000;
+
+const USAGE: &str =
+    "usage: simulate (<script> | --replay <file>) [--record <file>] [--max-ticks N]";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(2),
+        Err(err) => {
+            eprintln!("simulate: {:#}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run the race and print the outcome. Returns `false` if the race did not finish.
+fn run() -> Result<bool> {
+    let mut args = std::env::args().skip(1);
+    let mut script_file = None;
+    let mut replay_file = None;
+    let mut record_file = None;
+    let mut max_ticks = DEFAULT_MAX_TICKS;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("{} needs a value", arg));
+        match arg.as_str() {
+            "--max-ticks" => max_ticks = value()?.parse()?,
+            "--replay" => replay_file = Some(value()?),
+            "--record" => record_file = Some(value()?),
+            s if s.starts_with("--") => return Err(anyhow!(USAGE)),
+            _ => script_file = Some(arg),
+        }
+    }
+
+    let stage = GameStage::new(
+        Rc::new(SilentAudio::default()),
+        Rc::new(FileAssets::new(WEB_ROOT)),
+        Rc::new(MemoryKeyBindings::default()),
+    );
+    let progress = RefCell::new(LoadProgress::default());
+    let mut game = futures::executor::block_on(stage.load(&progress)).inspect_err(|_| {
+        for error in progress.borrow().errors() {
+            eprintln!("simulate: {}", error);
+        }
+    })?;
+    let mut input = match (script_file, replay_file) {
+        (Some(script_file), None) => ScriptedInput::parse(&read(&script_file)?)?,
+        (None, Some(replay_file)) => {
+            game.start_playback(Replay::decode(&read(&replay_file)?)?);
+            ScriptedInput::parse("")?
+        }
+        _ => return Err(anyhow!(USAGE)),
+    };
+
+    for tick in 0..max_ticks {
+        let mut actions = ActionState::new();
+        input.process_input(&mut actions);
+        game.update(&actions);
+
+        if let Some(outcome) = game.outcome() {
+            println!(
+                "result: {}",
+                if outcome.cleared { "clear" } else { "crash" }
+            );
+            println!("time: {:.3}", outcome.time as f32 / 1000.0);
+            println!(
+                "position: {:.1}, {:.1}",
+                outcome.position.x, outcome.position.y
+            );
+            println!("distance: {:.1}", outcome.distance);
+            println!("ticks: {}", tick + 1);
+            if let (Some(record_file), Some(replay)) = (&record_file, game.replay()) {
+                std::fs::write(record_file, replay.encode() + "\n")
+                    .map_err(|err| anyhow!("could not write {}: {}", record_file, err))?;
+            }
+            return Ok(true);
+        }
+    }
+    println!("result: timeout");
+    println!("ticks: {}", max_ticks);
+    Ok(false)
+}
+
+fn read(file: &str) -> Result<String> {
+    std::fs::read_to_string(file).map_err(|err| anyhow!("could not read {}: {}", file, err))
+}
@@ -1,21 +1,28 @@
-use anyhow::{anyhow, Result};
-use std::future::Future;
+use anyhow::{Result, anyhow};
 use js_sys::ArrayBuffer;
-use wasm_bindgen::{
-    closure::WasmClosure, prelude::Closure, JsCast, JsValue,
-};
+use std::future::Future;
+use wasm_bindgen::{JsCast, JsValue, closure::WasmClosure, prelude::Closure};
 use wasm_bindgen_futures::JsFuture;
 
 use web_sys::{
-    CanvasRenderingContext2d, Document, HtmlCanvasElement, Response, Window,
+    CanvasRenderingContext2d, Document, FontFace, HtmlCanvasElement, Response, Storage, Url,
+    UrlSearchParams, Window,
 };
 
+#[cfg(target_arch = "wasm32")]
 macro_rules! log {
     ( $( $t:tt )* ) => {
         web_sys::console::log_1(&format!( $( $t )* ).into());
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+macro_rules! log {
+    ( $( $t:tt )* ) => {
+        eprintln!( $( $t )* );
+    }
+}
+
 pub fn window() -> Result<Window> {
     web_sys::window().ok_or_else(|| anyhow!("No Window Found"))
 }
@@ -48,6 +55,69 @@ pub fn context() -> Result<CanvasRenderingContext2d> {
         })
 }
 
+/// Size of the browser window's layout viewport in CSS pixels
+pub fn window_size() -> Result<(f64, f64)> {
+    let window = window()?;
+    let size = |value: Result<JsValue, JsValue>| {
+        value
+            .ok()
+            .and_then(|value| value.as_f64())
+            .ok_or_else(|| anyhow!("Could not get window size"))
+    };
+    Ok((size(window.inner_width())?, size(window.inner_height())?))
+}
+
+pub fn local_storage() -> Result<Storage> {
+    window()?
+        .local_storage()
+        .map_err(|err| anyhow!("Error getting local storage {:#?}", err))?
+        .ok_or_else(|| anyhow!("No Local Storage Found"))
+}
+
+pub fn load_item(key: &str) -> Result<Option<String>> {
+    local_storage()?
+        .get_item(key)
+        .map_err(|err| anyhow!("Error loading {} from local storage {:#?}", key, err))
+}
+
+pub fn save_item(key: &str, value: &str) -> Result<()> {
+    local_storage()?
+        .set_item(key, value)
+        .map_err(|err| anyhow!("Error saving {} to local storage {:#?}", key, err))
+}
+
+pub fn query_param(name: &str) -> Result<Option<String>> {
+    let search = window()?
+        .location()
+        .search()
+        .map_err(|err| anyhow!("Error reading location search {:#?}", err))?;
+    Ok(UrlSearchParams::new_with_str(&search)
+        .map_err(|err| anyhow!("Error parsing location search {:#?}", err))?
+        .get(name))
+}
+
+/// URL that relative links on the page are resolved against
+pub fn base_uri() -> Result<String> {
+    document()?
+        .base_uri()
+        .map_err(|err| anyhow!("Error reading base URI {:#?}", err))?
+        .ok_or_else(|| anyhow!("Document has no base URI"))
+}
+
+/// Resolves `url` against `base`, the way a link on the page would be
+pub fn resolve_url(url: &str, base: &str) -> Result<String> {
+    Url::new_with_base(url, base)
+        .map(|url| url.href())
+        .map_err(|err| anyhow!("Invalid URL {} {:#?}", url, err))
+}
+
+pub fn gamepads() -> Result<js_sys::Array> {
+    window()?
+        .navigator()
+        .get_gamepads()
+        .map_err(|err| anyhow!("Error getting gamepads {:#?}", err))
+}
+
 pub fn spawn_local<F>(future: F)
 where
     F: Future<Output = ()> + 'static,
@@ -62,10 +132,47 @@ pub async fn fetch_with_str(resource: &str) -> Result<JsValue> {
 }
 
 pub async fn fetch_response(resource: &str) -> Result<Response> {
-    fetch_with_str(resource)
+    let response: Response = fetch_with_str(resource)
         .await?
         .dyn_into()
-        .map_err(|err| anyhow!("error converting fetch to Response {:#?}", err))
+        .map_err(|err| anyhow!("error converting fetch to Response {:#?}", err))?;
+    if !response.ok() {
+        return Err(anyhow!(
+            "HTTP status {} for {}",
+            response.status(),
+            resource
+        ));
+    }
+    Ok(response)
+}
+
+pub async fn fetch_text(resource: &str) -> Result<String> {
+    let text = fetch_response(resource)
+        .await?
+        .text()
+        .map_err(|err| anyhow!("Error loading text {:#?}", err))?;
+
+    JsFuture::from(text)
+        .await
+        .map_err(|err| anyhow!("Error converting text into a future {:#?}", err))?
+        .as_string()
+        .ok_or_else(|| anyhow!("Error converting raw JSValue to String"))
+}
+
+/// Loads a font file and registers it with the document as `family`
+pub async fn load_font(family: &str, resource: &str) -> Result<()> {
+    let face = FontFace::new_with_str(family, &format!("url({})", resource))
+        .map_err(|err| anyhow!("Error creating font face {:#?}", err))?;
+    let loaded = face
+        .load()
+        .map_err(|err| anyhow!("Error loading font {:#?}", err))?;
+    JsFuture::from(loaded)
+        .await
+        .map_err(|err| anyhow!("Error loading font {:#?}", err))?;
+    document()?
+        .fonts()
+        .add(&face)
+        .map_err(|err| anyhow!("Error adding font {:#?}", err))
 }
 
 pub async fn fetch_array_buffer(resource: &str) -> Result<ArrayBuffer> {
@@ -81,7 +188,6 @@ pub async fn fetch_array_buffer(resource: &str) -> Result<ArrayBuffer> {
         .map_err(|err| anyhow!("Error converting raw JSValue to ArrayBuffer {:#?}", err))
 }
 
-
 pub type LoopClosure = Closure<dyn FnMut(f64)>;
 pub fn create_raf_closure(f: impl FnMut(f64) + 'static) -> LoopClosure {
     closure_wrap(Box::new(f))
@@ -102,4 +208,4 @@ pub fn now() -> Result<f64> {
         .performance()
         .ok_or_else(|| anyhow!("Performance object not found"))?
         .now())
-}
\ No newline at end of file
+}
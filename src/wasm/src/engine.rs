@@ -1,19 +1,14 @@
-use crate::browser::{self, LoopClosure};
-use crate::game::CANVAS_HEIGHT;
-use crate::sound;
-//use num_traits::FromPrimitive;
+use crate::geometry::Rect;
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
-use futures::channel::{
-    mpsc::{UnboundedReceiver, unbounded},
-    //oneshot::channel,
-};
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
-use wasm_bindgen::JsCast;
-use web_sys::CanvasRenderingContext2d;
-use web_sys::{AudioBuffer, AudioContext};
+use futures::future::join_all;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString};
 
-pub const FONT_COLOR: &str = "green";
+pub const FRAME_SIZE: f64 = 1.0 / 60.0 * 1000.0;
 
 #[derive(Clone, Copy, Default)]
 pub struct Point {
@@ -49,189 +44,1270 @@ impl Line {
     }
 }
 
-pub struct Renderer {
-    context: CanvasRenderingContext2d,
+/// Drawing surface used by the game. The y axis points up from the bottom edge.
+pub trait Renderer {
+    /// Fill an area with `color`, e.g. the theme's background
+    fn clear(&self, point: &Point, width: f32, height: f32, color: &str);
+    fn text(&self, point: &Point, text: &str, color: &str, font: &str, align: &str);
+    fn line(&self, p: &Point, q: &Point, color: &str);
+    /// Draw every row of `sprite` with its anchor at `point`. Glyphs without a
+    /// color of their own are drawn in `color`.
+    fn sprite(&self, point: &Point, sprite: &Sprite, color: &str);
 }
 
-impl Renderer {
-    pub fn clear(&self, point: &Point, width: f32, height: f32) {
-        self.context
-            .clear_rect(point.x.into(), point.y.into(), width as f64, height as f64);
+///
+/// Named colors the game is drawn with.
+///
+/// Drawing code picks colors from the current theme instead of hard-coding
+/// them, so the whole game can be recolored by switching themes.
+pub struct Theme {
+    /// Shown to the player when picking a theme
+    pub name: &'static str,
+    pub walls: &'static str,
+    pub cart: &'static str,
+    /// Translucent cart replaying the best run
+    pub ghost: &'static str,
+    /// Trees and other decorations beside the track
+    pub scenery: &'static str,
+    /// Texts, menus and on-screen controls
+    pub hud: &'static str,
+    pub goal: &'static str,
+    pub background: &'static str,
+}
+
+/// Themes the player can switch between. The first one is the default.
+pub static THEMES: [Theme; 4] = [
+    Theme {
+        name: "Classic",
+        walls: "green",
+        cart: "green",
+        ghost: "rgba(0, 128, 0, 0.35)",
+        scenery: "green",
+        hud: "green",
+        goal: "green",
+        background: "black",
+    },
+    Theme {
+        name: "Amber CRT",
+        walls: "#ffb000",
+        cart: "#ffcc00",
+        ghost: "rgba(255, 176, 0, 0.35)",
+        scenery: "#cc8400",
+        hud: "#ffb000",
+        goal: "#ffd966",
+        background: "#1a0f00",
+    },
+    Theme {
+        name: "High Contrast",
+        walls: "white",
+        cart: "yellow",
+        ghost: "rgba(255, 255, 255, 0.45)",
+        scenery: "cyan",
+        hud: "white",
+        goal: "white",
+        background: "black",
+    },
+    // Okabe-Ito colors, which stay apart for the common color vision deficiencies
+    Theme {
+        name: "Colorblind Safe",
+        walls: "#56b4e9",
+        cart: "#e69f00",
+        ghost: "rgba(230, 159, 0, 0.4)",
+        scenery: "#009e73",
+        hud: "#f0e442",
+        goal: "#cc79a7",
+        background: "black",
+    },
+];
+
+impl Theme {
+    /// Gets the theme after this one in `THEMES`, wrapping around
+    pub fn next(&self) -> &'static Theme {
+        let index = THEMES
+            .iter()
+            .position(|theme| theme.name == self.name)
+            .unwrap_or_default();
+        &THEMES[(index + 1) % THEMES.len()]
+    }
+}
+
+///
+/// Multi-line ASCII art drawn by `Renderer::sprite` in one call.
+///
+/// Rows are drawn top to bottom, each one centered on the anchor. The text form
+/// starts with `key = value` settings, followed by the art after `[rows]` and an
+/// optional color mask of the same shape after `[colors]`. Every character of
+/// the mask other than a space picks the color declared as `color.<char>`; the
+/// other glyphs are drawn in the color given to `Renderer::sprite`. Lines
+/// starting with `#` before `[rows]` are comments.
+///
+/// ```text
+/// font = 24px sans-serif
+/// color.r = red
+/// line_height = 18
+/// anchor = 0, 0
+/// [rows]
+/// o❚o
+/// [colors]
+///  r
+/// ```
+#[derive(Clone)]
+pub struct Sprite {
+    /// Glyphs of each row with their index into `palette`
+    rows: Vec<Vec<(char, usize)>>,
+    /// Colors of the glyphs. The first one stands for the color the sprite is drawn in.
+    palette: Vec<String>,
+    font: String,
+    /// Distance between the centers of two rows
+    line_height: f32,
+    /// Offset from the drawing position to the center of the first row
+    anchor: Point,
+}
+
+impl Sprite {
+    /// Parses a sprite from its text form
+    pub fn parse(text: &str) -> Result<Sprite> {
+        let mut font = None;
+        let mut line_height = None;
+        let mut anchor = Point::default();
+        let mut palette = vec![String::new()];
+        let mut keys = vec![' '];
+        let mut rows: Vec<&str> = vec![];
+        let mut mask: Vec<&str> = vec![];
+        let mut section = "";
+        for line in text.lines() {
+            let line = line.trim_end_matches('\r');
+            match (section, line.trim()) {
+                (_, "[rows]") => section = "rows",
+                (_, "[colors]") => section = "colors",
+                ("rows", _) => rows.push(line),
+                ("colors", _) => mask.push(line),
+                (_, setting) if setting.is_empty() || setting.starts_with('#') => {}
+                (_, setting) => {
+                    let (key, value) = setting
+                        .split_once('=')
+                        .map(|(key, value)| (key.trim(), value.trim()))
+                        .ok_or_else(|| anyhow!("Invalid sprite setting {:?}", setting))?;
+                    match key {
+                        "font" => font = Some(value.to_string()),
+                        "line_height" => {
+                            line_height =
+                                Some(value.parse().map_err(|_| {
+                                    anyhow!("Invalid sprite line height {:?}", value)
+                                })?)
+                        }
+                        "anchor" => {
+                            anchor = value
+                                .split_once(',')
+                                .and_then(|(x, y)| {
+                                    Some(Point::new(x.trim().parse().ok()?, y.trim().parse().ok()?))
+                                })
+                                .ok_or_else(|| anyhow!("Invalid sprite anchor {:?}", value))?
+                        }
+                        _ => match key
+                            .strip_prefix("color.")
+                            .map(|key| key.chars().collect::<Vec<_>>())
+                        {
+                            Some(key) if key.len() == 1 && key[0] != ' ' => {
+                                keys.push(key[0]);
+                                palette.push(value.to_string());
+                            }
+                            _ => return Err(anyhow!("Unknown sprite setting {:?}", key)),
+                        },
+                    }
+                }
+            }
+        }
+        while rows.last().is_some_and(|row| row.trim().is_empty()) {
+            rows.pop();
+        }
+        if rows.is_empty() {
+            return Err(anyhow!("Sprite has no [rows]"));
+        }
+        let rows = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                let mut colors = mask.get(index).copied().unwrap_or_default().chars();
+                row.chars()
+                    .map(|glyph| {
+                        let key = colors.next().unwrap_or(' ');
+                        keys.iter()
+                            .position(|color_key| *color_key == key)
+                            .map(|color| (glyph, color))
+                            .ok_or_else(|| anyhow!("Sprite color {:?} is not declared", key))
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Sprite {
+            rows,
+            palette,
+            font: font.ok_or_else(|| anyhow!("Sprite has no font"))?,
+            line_height: line_height.ok_or_else(|| anyhow!("Sprite has no line_height"))?,
+            anchor,
+        })
+    }
+
+    pub fn font(&self) -> &str {
+        &self.font
+    }
+
+    /// Number of rows
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Center of row `index` when the sprite is drawn at `point`
+    pub fn row_position(&self, point: &Point, index: usize) -> Point {
+        Point {
+            x: point.x + self.anchor.x,
+            y: point.y + self.anchor.y - self.line_height * index as f32,
+        }
+    }
+
+    /// Splits row `index` into runs of glyphs sharing a color, with the sprite drawn in `color`
+    pub fn runs<'a>(&'a self, index: usize, color: &'a str) -> Vec<(String, &'a str)> {
+        let mut runs: Vec<(String, usize)> = vec![];
+        for &(glyph, color) in &self.rows[index] {
+            match runs.last_mut() {
+                Some((text, last)) if *last == color => text.push(glyph),
+                _ => runs.push((glyph.to_string(), color)),
+            }
+        }
+        runs.into_iter()
+            .map(|(text, index)| match index {
+                0 => (text, color),
+                _ => (text, self.palette[index].as_str()),
+            })
+            .collect()
+    }
+
+    /// Copy of the sprite without its own glyph colors, drawn entirely in one color
+    pub fn tinted(&self) -> Sprite {
+        Sprite {
+            rows: self
+                .rows
+                .iter()
+                .map(|row| row.iter().map(|(glyph, _)| (*glyph, 0)).collect())
+                .collect(),
+            palette: vec![String::new()],
+            ..self.clone()
+        }
+    }
+
+    /// Copy of the sprite drawn `zoom` times as large
+    pub fn scaled(&self, zoom: f32) -> Sprite {
+        Sprite {
+            font: scale_font(&self.font, zoom),
+            line_height: self.line_height * zoom,
+            anchor: Point::new(self.anchor.x * zoom, self.anchor.y * zoom),
+            ..self.clone()
+        }
+    }
+}
+
+///
+/// View of the world that pieces are drawn through.
+///
+/// Pieces keep their world positions and the camera decides which part of the
+/// world is on screen and how large it is drawn.
+#[derive(Clone, Copy)]
+pub struct Camera {
+    /// World position shown at the bottom-left corner of the screen
+    pub position: Point,
+    /// Screen units per world unit
+    pub zoom: f32,
+}
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: Point::default(),
+            zoom: 1.0,
+        }
+    }
+}
+impl Camera {
+    /// Converts a world position to a screen position
+    pub fn to_screen(&self, point: &Point) -> Point {
+        Point {
+            x: (point.x - self.position.x) * self.zoom,
+            y: (point.y - self.position.y) * self.zoom,
+        }
+    }
+    /// Moves the camera so that `target` is drawn at the screen position `anchor`
+    pub fn follow(&mut self, target: &Point, anchor: &Point) {
+        self.position = Point {
+            x: target.x - anchor.x / self.zoom,
+            y: target.y - anchor.y / self.zoom,
+        };
+    }
+    /// World area shown on a screen of `width` by `height` screen units
+    pub fn visible_area(&self, width: f32, height: f32) -> Rect {
+        Rect {
+            min: self.position,
+            max: Point {
+                x: self.position.x + width / self.zoom,
+                y: self.position.y + height / self.zoom,
+            },
+        }
+    }
+    /// Wraps `renderer` so that it takes world positions
+    pub fn view<'a>(&'a self, renderer: &'a dyn Renderer) -> CameraRenderer<'a> {
+        CameraRenderer {
+            camera: self,
+            renderer,
+        }
+    }
+}
+
+/// Renderer that converts world positions to screen positions through a `Camera`.
+pub struct CameraRenderer<'a> {
+    camera: &'a Camera,
+    renderer: &'a dyn Renderer,
+}
+impl Renderer for CameraRenderer<'_> {
+    fn clear(&self, point: &Point, width: f32, height: f32, color: &str) {
+        self.renderer.clear(
+            &self.camera.to_screen(point),
+            width * self.camera.zoom,
+            height * self.camera.zoom,
+            color,
+        );
+    }
+    fn text(&self, point: &Point, text: &str, color: &str, font: &str, align: &str) {
+        self.renderer.text(
+            &self.camera.to_screen(point),
+            text,
+            color,
+            &scale_font(font, self.camera.zoom),
+            align,
+        );
+    }
+    fn line(&self, p: &Point, q: &Point, color: &str) {
+        self.renderer
+            .line(&self.camera.to_screen(p), &self.camera.to_screen(q), color);
+    }
+    fn sprite(&self, point: &Point, sprite: &Sprite, color: &str) {
+        let point = self.camera.to_screen(point);
+        if self.camera.zoom == 1.0 {
+            self.renderer.sprite(&point, sprite, color);
+        } else {
+            self.renderer
+                .sprite(&point, &sprite.scaled(self.camera.zoom), color);
+        }
+    }
+}
+
+/// Scales the pixel size at the start of a CSS font, e.g. `24px sans-serif`.
+/// Fonts without a pixel size are kept as they are.
+fn scale_font(font: &str, zoom: f32) -> String {
+    if zoom == 1.0 {
+        return font.to_string();
+    }
+    match font
+        .split_once("px")
+        .and_then(|(size, family)| Some((size.parse::<f32>().ok()?, family)))
+    {
+        Some((size, family)) => format!("{}px{}", size * zoom, family),
+        None => font.to_string(),
+    }
+}
+
+/// Handle to a sound loaded by an `Audio` backend.
+#[derive(Clone, Copy)]
+pub struct Sound {
+    pub id: usize,
+    /// Mixer bus the sound plays on
+    pub bus: Bus,
+}
+
+/// Volume control of the mixer. Music and effects play on their own bus,
+/// and both feed the master bus.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Display, EnumIter, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Bus {
+    Master,
+    Music,
+    Effects,
+}
+
+/// Step the volume sliders move by
+pub const VOLUME_STEP: f32 = 0.1;
+
+///
+/// Volumes of the mixer buses, from 0 to 1, and whether sound is muted.
+///
+/// The text form has one setting per line, `<bus>=<volume>` or
+/// `muted=<true|false>`, e.g. `music=0.5`. Settings missing from the text
+/// keep their defaults.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MixerSettings {
+    master: f32,
+    music: f32,
+    effects: f32,
+    pub muted: bool,
+}
+
+impl Default for MixerSettings {
+    fn default() -> Self {
+        MixerSettings {
+            master: 1.0,
+            music: 1.0,
+            effects: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl MixerSettings {
+    pub fn volume(&self, bus: Bus) -> f32 {
+        match bus {
+            Bus::Master => self.master,
+            Bus::Music => self.music,
+            Bus::Effects => self.effects,
+        }
+    }
+
+    /// Sets the volume of a bus, rounded to a `VOLUME_STEP` and kept within 0 to 1
+    pub fn set_volume(&mut self, bus: Bus, volume: f32) {
+        let volume = ((volume / VOLUME_STEP).round() * VOLUME_STEP).clamp(0.0, 1.0);
+        match bus {
+            Bus::Master => self.master = volume,
+            Bus::Music => self.music = volume,
+            Bus::Effects => self.effects = volume,
+        }
     }
-    pub fn text(&self, point: &Point, text: &str, color: &str, font: &str, align: &str) {
-        self.context.set_fill_style_str(color);
-        self.context.set_text_align(align);
-        self.context.set_text_baseline("middle");
-        self.context.set_font(font);
-        let _ = self
-            .context
-            .fill_text(text, point.x as f64, CANVAS_HEIGHT as f64 - point.y as f64);
+
+    /// Gain a bus plays at. Muting silences the master bus.
+    pub fn gain(&self, bus: Bus) -> f32 {
+        if bus == Bus::Master && self.muted {
+            0.0
+        } else {
+            self.volume(bus)
+        }
     }
-    pub fn line(&self, p: &Point, q: &Point) {
-        self.context.begin_path();
-        self.context.set_stroke_style_str(FONT_COLOR);
-        self.context
-            .move_to(p.x.into(), CANVAS_HEIGHT as f64 - p.y as f64);
-        self.context
-            .line_to(q.x.into(), CANVAS_HEIGHT as f64 - q.y as f64);
-        self.context.close_path();
-        self.context.stroke();
+
+    /// Encodes the settings into their text form
+    pub fn encode(&self) -> String {
+        Bus::iter()
+            .map(|bus| format!("{}={:.2}\n", bus, self.volume(bus)))
+            .chain([format!("muted={}\n", self.muted)])
+            .collect()
+    }
+
+    /// Decodes settings from their text form
+    pub fn decode(text: &str) -> Result<Self> {
+        let mut settings = MixerSettings::default();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid mixer setting {:?}", line))?;
+            let (name, value) = (name.trim(), value.trim());
+            if name == "muted" {
+                settings.muted = value
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid mute setting {:?}", value))?;
+                continue;
+            }
+            let bus: Bus = name
+                .parse()
+                .map_err(|_| anyhow!("Unknown mixer bus {:?}", name))?;
+            let volume: f32 = value
+                .parse()
+                .map_err(|_| anyhow!("Invalid volume {:?}", value))?;
+            settings.set_volume(bus, volume);
+        }
+        Ok(settings)
     }
 }
 
+/// A sound that was started by an `Audio` backend. Dropping the handle
+/// leaves the sound playing.
+pub trait Playback {
+    /// Stop the sound right away
+    fn stop(&self) -> Result<()>;
+    /// Lower the volume to silence over `seconds`, then stop
+    fn fade_out(&self, seconds: f64) -> Result<()>;
+    /// Speed and pitch of the sound, 1 being as recorded
+    fn set_playback_rate(&self, rate: f32) -> Result<()>;
+    /// Start the sound over when it ends, or let it end
+    fn set_looping(&self, looping: bool) -> Result<()>;
+    /// Place the sound from the left speaker at -1 to the right one at 1
+    fn set_pan(&self, pan: f32) -> Result<()>;
+    /// Whether the sound has not ended yet
+    fn is_playing(&self) -> bool;
+}
+
+/// A synthesized engine drone that was started by an `Audio` backend.
+/// Dropping the handle leaves the drone playing.
+pub trait EngineSound {
+    /// Pitch and volume of the drone, from idling at 0 to full speed at 1
+    fn set_speed(&self, speed: f32) -> Result<()>;
+    /// Lower the volume to silence over `seconds`, then stop
+    fn fade_out(&self, seconds: f64) -> Result<()>;
+}
+
 #[async_trait(?Send)]
-pub trait Game {
-    async fn initialize(&self) -> Result<Box<dyn Game>>;
-    fn update(&mut self, keystate: &KeyState);
-    fn draw(&self, renderer: &Renderer);
+pub trait Audio {
+    async fn load_sound(&self, filename: &str, bus: Bus) -> Result<Sound>;
+    fn play_sound(&self, sound: &Sound) -> Result<Box<dyn Playback>>;
+    fn play_looping_sound(&self, sound: &Sound) -> Result<Box<dyn Playback>>;
+    /// Start an engine drone on the effects bus, idling until its speed is set
+    fn play_engine_sound(&self) -> Result<Box<dyn EngineSound>>;
+    /// Stop all playback until `resume`
+    fn suspend(&self) -> Result<()>;
+    fn resume(&self) -> Result<()>;
+    /// Current volumes of the mixer
+    fn mixer(&self) -> MixerSettings;
+    /// Change the volumes of the mixer. Backends keep them for the next session.
+    fn set_mixer(&self, settings: MixerSettings) -> Result<()>;
+    /// Whether the sounds can be heard at all
+    fn is_available(&self) -> bool {
+        true
+    }
 }
 
-const FRAME_SIZE: f64 = 1.0 / 60.0 * 1000.0;
-pub struct GameLoop {
-    last_frame: f64,
-    accumulated_delta: f64,
+/// Kind of file an asset is loaded from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AssetKind {
+    /// Sound effect decoded by the `Audio` backend
+    Sound,
+    /// Song decoded by the `Audio` backend, played on the music bus
+    Music,
+    /// Font face registered under the asset id, for use in `font` strings
+    Font,
+    /// Sprite in the text form read by `Sprite::parse`
+    Sprite,
+    /// Track layout in text form
+    Track,
 }
-type SharedLoopClosure = Rc<RefCell<Option<LoopClosure>>>;
 
-impl GameLoop {
-    pub async fn start(game: impl Game + 'static) -> Result<()> {
-        let mut keyevent_receiver = prepare_input()?;
-        let mut game = game.initialize().await?;
-        let mut game_loop = GameLoop {
-            last_frame: browser::now()?,
-            accumulated_delta: 0.0,
-        };
+/// A file the game needs before it can start
+pub struct AssetFile {
+    /// Logical id the asset is looked up by and reported under when it fails
+    pub id: String,
+    pub kind: AssetKind,
+    /// Location of the file, relative to the asset base URL
+    pub path: String,
+}
 
-        let renderer = Renderer {
-            context: browser::context()?,
-        };
+///
+/// List of the game's assets by logical id, read from a JSON file.
+///
+/// Paths are relative to the asset base URL, so the same manifest works
+/// wherever the game is hosted.
+///
+/// ```json
+/// {
+///     "sounds": { "brake": "assets/beep-7.wav" },
+///     "music": { "background_music": "assets/background_song.mp3" },
+///     "fonts": { "myfont": "static/FFFFORWA.ttf" },
+///     "sprites": { "cart": "assets/sprites/cart.txt" },
+///     "tracks": { "main": "assets/track.txt" }
+/// }
+/// ```
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Manifest {
+    sounds: BTreeMap<String, String>,
+    music: BTreeMap<String, String>,
+    fonts: BTreeMap<String, String>,
+    sprites: BTreeMap<String, String>,
+    tracks: BTreeMap<String, String>,
+}
+impl Manifest {
+    pub fn parse(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|err| anyhow!("Invalid asset manifest: {}", err))
+    }
+    /// Every file listed in the manifest
+    pub fn files(&self) -> Vec<AssetFile> {
+        [
+            (AssetKind::Sound, &self.sounds),
+            (AssetKind::Music, &self.music),
+            (AssetKind::Font, &self.fonts),
+            (AssetKind::Sprite, &self.sprites),
+            (AssetKind::Track, &self.tracks),
+        ]
+        .into_iter()
+        .flat_map(|(kind, entries)| {
+            entries.iter().map(move |(id, path)| AssetFile {
+                id: id.clone(),
+                kind,
+                path: path.clone(),
+            })
+        })
+        .collect()
+    }
+}
 
-        let f: SharedLoopClosure = Rc::new(RefCell::new(None));
-        let g = f.clone();
+/// Platform side of the asset manager, for the assets that are not sounds
+#[async_trait(?Send)]
+pub trait AssetLoader {
+    /// Where a file given relative to the asset base URL is loaded from
+    fn locate(&self, path: &str) -> String;
+    /// Load a font face and make it available to the renderer as `family`
+    async fn load_font(&self, family: &str, url: &str) -> Result<()>;
+    async fn load_text(&self, url: &str) -> Result<String>;
+}
 
-        let mut keystate = KeyState::new();
-        *g.borrow_mut() = Some(browser::create_raf_closure(move |perf: f64| {
-            process_input(&mut keystate, &mut keyevent_receiver);
+/// Loading state shared between the asset manager and the loading screen
+#[derive(Default)]
+pub struct LoadProgress {
+    total: usize,
+    loaded: usize,
+    errors: Vec<String>,
+}
+impl LoadProgress {
+    /// Share of the assets loaded so far, from 0 to 1
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.loaded as f32 / self.total as f32
+        }
+    }
+    /// Problems met while loading, one line each
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+    /// Record a problem to show on the loading screen
+    pub fn fail(&mut self, message: String) {
+        self.errors.push(message);
+    }
+}
+
+enum Asset {
+    Sound(Sound),
+    Font,
+    Sprite(Sprite),
+    Track(String),
+}
 
-            game_loop.accumulated_delta += perf - game_loop.last_frame;
-            while game_loop.accumulated_delta > FRAME_SIZE {
-                game.update(&keystate);
-                game_loop.accumulated_delta -= FRAME_SIZE;
+/// Sounds, sprites and tracks loaded by the asset manager, looked up by id.
+#[derive(Default)]
+pub struct Assets {
+    sounds: HashMap<String, Sound>,
+    sprites: HashMap<String, Sprite>,
+    tracks: HashMap<String, String>,
+}
+impl Assets {
+    ///
+    /// Loads every file in parallel, counting each one in `progress` as it finishes.
+    ///
+    /// Every file is attempted even when another one fails, so that all the
+    /// failures end up in `progress` together.
+    ///
+    /// # Arguments
+    /// * `files` - Files to load
+    /// * `audio` - Backend that loads the sounds
+    /// * `loader` - Backend that locates the files and loads everything else
+    /// * `progress` - Loading state to report to
+    /// * `optional` - Whether the caller can do without the files, so that a
+    ///   failed one is logged and counted as done instead of shown as a problem
+    ///
+    /// # Returns
+    /// The loaded assets, or an error if any of the files failed
+    pub async fn load(
+        files: &[AssetFile],
+        audio: &dyn Audio,
+        loader: &dyn AssetLoader,
+        progress: &RefCell<LoadProgress>,
+        optional: bool,
+    ) -> Result<Assets> {
+        progress.borrow_mut().total += files.len();
+        let total = files.len();
+        let results = join_all(files.iter().map(|file| async move {
+            let url = loader.locate(&file.path);
+            let result = match file.kind {
+                AssetKind::Sound => audio.load_sound(&url, Bus::Effects).await.map(Asset::Sound),
+                AssetKind::Music => audio.load_sound(&url, Bus::Music).await.map(Asset::Sound),
+                AssetKind::Font => loader.load_font(&file.id, &url).await.map(|_| Asset::Font),
+                AssetKind::Sprite => loader
+                    .load_text(&url)
+                    .await
+                    .and_then(|text| Sprite::parse(&text))
+                    .map(Asset::Sprite),
+                AssetKind::Track => loader.load_text(&url).await.map(Asset::Track),
+            };
+            let mut progress = progress.borrow_mut();
+            match &result {
+                Ok(_) => progress.loaded += 1,
+                Err(err) if optional => {
+                    log!("Skipping {} ({}): {:#}", file.id, url, err);
+                    progress.loaded += 1;
+                }
+                Err(err) => progress.fail(format!("{} ({}): {:#}", file.id, url, err)),
             }
-            let _last_frame = browser::now().unwrap();
-            game.draw(&renderer);
-            game_loop.last_frame = _last_frame;
-
-            let _ = browser::request_animation_frame(f.borrow().as_ref().unwrap());
-        }));
-
-        browser::request_animation_frame(
-            g.borrow()
-                .as_ref()
-                .ok_or_else(|| anyhow!("GameLoop: Loop is None"))?,
-        )?;
-        Ok(())
+            result.map(|asset| (file.id.clone(), asset))
+        }))
+        .await;
+
+        let failed = results.iter().filter(|result| result.is_err()).count();
+        if failed > 0 {
+            return Err(anyhow!(
+                "{} of {} assets could not be loaded",
+                failed,
+                total
+            ));
+        }
+        let mut assets = Assets::default();
+        for (id, asset) in results.into_iter().flatten() {
+            match asset {
+                Asset::Sound(sound) => {
+                    assets.sounds.insert(id, sound);
+                }
+                Asset::Font => {}
+                Asset::Sprite(sprite) => {
+                    assets.sprites.insert(id, sprite);
+                }
+                Asset::Track(track) => {
+                    assets.tracks.insert(id, track);
+                }
+            }
+        }
+        Ok(assets)
+    }
+    pub fn sound(&self, id: &str) -> Result<Sound> {
+        self.sounds
+            .get(id)
+            .copied()
+            .ok_or_else(|| anyhow!("No sound {:?} in the asset manifest", id))
     }
+    pub fn sprite(&self, id: &str) -> Result<Sprite> {
+        self.sprites
+            .get(id)
+            .cloned()
+            .ok_or_else(|| anyhow!("No sprite {:?} in the asset manifest", id))
+    }
+    pub fn track(&self, id: &str) -> Result<&str> {
+        self.tracks
+            .get(id)
+            .map(String::as_str)
+            .ok_or_else(|| anyhow!("No track {:?} in the asset manifest", id))
+    }
+}
+
+#[async_trait(?Send)]
+pub trait Game {
+    /// Load the assets, reporting to `progress`, and set up the game
+    async fn initialize(&self, progress: &RefCell<LoadProgress>) -> Result<Box<dyn Game>>;
+    fn update(&mut self, actions: &ActionState);
+    fn draw(&self, renderer: &dyn Renderer);
+    /// Colors the game is currently drawn with
+    fn theme(&self) -> &'static Theme {
+        &THEMES[0]
+    }
+    /// Pause the game and silence it when the player cannot see it
+    fn pause(&mut self) {}
+    /// Let the sound play again once the player can see the game. A race
+    /// paused by `pause` stays paused until the player resumes it.
+    fn show(&mut self) {}
+    /// Draw the screen shown while `initialize` runs, or after it failed
+    fn draw_loading(&self, _renderer: &dyn Renderer, _progress: &LoadProgress) {}
 }
 
+#[derive(Default)]
 pub struct KeyState {
-    pressed_keys: HashMap<String, web_sys::KeyboardEvent>,
+    pressed_keys: HashSet<String>,
 }
 
 impl KeyState {
-    fn new() -> Self {
+    pub fn new() -> Self {
         KeyState {
-            pressed_keys: HashMap::new(),
+            pressed_keys: HashSet::new(),
         }
     }
     pub fn is_pressed(&self, code: &str) -> bool {
-        self.pressed_keys.contains_key(code)
+        self.pressed_keys.contains(code)
     }
 
-    fn set_pressed(&mut self, code: &str, event: web_sys::KeyboardEvent) {
-        self.pressed_keys.insert(code.into(), event);
+    pub fn set_pressed(&mut self, code: &str) {
+        self.pressed_keys.insert(code.into());
     }
 
-    fn set_released(&mut self, code: &str) {
+    pub fn set_released(&mut self, code: &str) {
         self.pressed_keys.remove(code);
     }
 }
 
-enum KeyPress {
-    KeyUp(web_sys::KeyboardEvent),
-    KeyDown(web_sys::KeyboardEvent),
+/// Platform input that reports the actions it holds on the current tick.
+pub trait Input {
+    /// Adds the actions held on this tick. Called once per simulation tick, so
+    /// an action held for a single call is seen by exactly one tick.
+    fn process_input(&mut self, actions: &mut ActionState);
+    /// Draw on-screen controls, for inputs that have them
+    fn draw(&self, _renderer: &dyn Renderer, _theme: &Theme) {}
 }
 
-fn process_input(state: &mut KeyState, keyevent_receiver: &mut UnboundedReceiver<KeyPress>) {
-    loop {
-        match keyevent_receiver.try_next() {
-            Ok(None) => break,
-            Err(_err) => break,
-            Ok(Some(evt)) => match evt {
-                KeyPress::KeyUp(evt) => state.set_released(&evt.code()),
-                KeyPress::KeyDown(evt) => state.set_pressed(&evt.code(), evt),
-            },
-        };
+/// Logical game actions that physical keys are bound to.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Display, EnumIter, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Action {
+    Accelerate,
+    Brake,
+    SteerLeft,
+    SteerRight,
+    Straighten,
+    /// Shares a key with Brake, so the screens after a race only take a new press
+    Confirm,
+    Pause,
+    WatchReplay,
+    MenuUp,
+    MenuDown,
+    NextTheme,
+    Mute,
+}
+
+/// Strength of a fully held action. Analog values are quantized to `1 / ACTION_LEVEL_MAX`
+/// steps so that a recorded level replays exactly.
+pub const ACTION_LEVEL_MAX: u8 = u8::MAX;
+
+///
+/// Actions held on the current tick.
+///
+/// Each action has a level from 0 (released) to `ACTION_LEVEL_MAX` (fully held).
+/// Keys and buttons hold actions fully, sticks and triggers hold them partially.
+/// When several inputs hold the same action the strongest one wins.
+#[derive(Clone, Default)]
+pub struct ActionState {
+    levels: HashMap<Action, u8>,
+    previous: HashSet<Action>,
+    /// `KeyboardEvent.code` of a key first pressed on this tick, for rebinding
+    key: Option<String>,
+}
+
+impl ActionState {
+    pub fn new() -> Self {
+        ActionState {
+            levels: HashMap::new(),
+            previous: HashSet::new(),
+            key: None,
+        }
+    }
+    pub fn is_active(&self, action: Action) -> bool {
+        self.level(action) > 0
+    }
+    /// Whether `action` became active on this tick. Needs `set_previous`.
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.is_active(action) && !self.previous.contains(&action)
+    }
+    /// Remembers which actions were active on the previous tick
+    pub fn set_previous(&mut self, previous: &ActionState) {
+        self.previous = previous
+            .levels
+            .iter()
+            .filter(|(_, level)| **level > 0)
+            .map(|(action, _)| *action)
+            .collect();
+    }
+    /// Gets how strongly `action` is held, from 0.0 to 1.0
+    pub fn value(&self, action: Action) -> f32 {
+        self.level(action) as f32 / ACTION_LEVEL_MAX as f32
+    }
+    pub fn level(&self, action: Action) -> u8 {
+        self.levels.get(&action).copied().unwrap_or_default()
+    }
+    pub fn set_active(&mut self, action: Action) {
+        self.set_level(action, ACTION_LEVEL_MAX);
+    }
+    /// Holds `action` with a strength from 0.0 to 1.0
+    pub fn set_value(&mut self, action: Action, value: f32) {
+        self.set_level(
+            action,
+            (value.clamp(0.0, 1.0) * ACTION_LEVEL_MAX as f32).round() as u8,
+        );
+    }
+    pub fn set_level(&mut self, action: Action, level: u8) {
+        if level > self.level(action) {
+            self.levels.insert(action, level);
+        }
+    }
+    /// Gets the key first pressed on this tick, whatever action it is bound to
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+    /// Reports a key first pressed on this tick
+    pub fn set_key(&mut self, code: &str) {
+        self.key = Some(code.to_string());
     }
 }
 
-// For Keypress Input
-fn prepare_input() -> Result<UnboundedReceiver<KeyPress>> {
-    let (keydown_sender, keyevent_receiver) = unbounded();
-    let keydown_sender = Rc::new(RefCell::new(keydown_sender));
-    let keyup_sender = Rc::clone(&keydown_sender);
-    let onkeydown = browser::closure_wrap(Box::new(move |keycode: web_sys::KeyboardEvent| {
-        let _ = keydown_sender
-            .borrow_mut()
-            .start_send(KeyPress::KeyDown(keycode));
-    }) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
+/// Keys that move through menus whatever the driving keys are
+const MENU_UP_KEY: &str = "ArrowUp";
+const MENU_DOWN_KEY: &str = "ArrowDown";
 
-    let onkeyup = browser::closure_wrap(Box::new(move |keycode: web_sys::KeyboardEvent| {
-        let _ = keyup_sender
-            .borrow_mut()
-            .start_send(KeyPress::KeyUp(keycode));
-    }) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
+/// Key layouts players can pick instead of binding every action by hand.
+pub const KEY_BINDING_PRESETS: [&str; 3] = ["arrows", "wasd", "left_handed"];
 
-    browser::canvas()?.set_onkeydown(Some(onkeydown.as_ref().unchecked_ref()));
-    browser::canvas()?.set_onkeyup(Some(onkeyup.as_ref().unchecked_ref()));
-    onkeydown.forget();
-    onkeyup.forget();
+///
+/// Mapping from `KeyboardEvent.code` values to actions.
+///
+/// The text form has one action per line, `<action>=<code>,<code>`, e.g.
+/// `steer_left=ArrowLeft,KeyA`. Actions missing from the text keep their
+/// default keys.
+#[derive(Clone)]
+pub struct KeyBindings {
+    bindings: HashMap<Action, Vec<String>>,
+}
 
-    Ok(keyevent_receiver)
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings::preset("arrows").expect("arrows preset exists")
+    }
 }
 
-#[derive(Clone)]
-pub struct Audio {
-    context: AudioContext,
+impl KeyBindings {
+    /// Gets one of the `KEY_BINDING_PRESETS` by name
+    pub fn preset(name: &str) -> Option<Self> {
+        let driving = match name {
+            "arrows" => ["ArrowUp", "Space", "ArrowLeft", "ArrowRight", "ArrowDown"],
+            "wasd" => ["KeyW", "Space", "KeyA", "KeyD", "KeyS"],
+            "left_handed" => ["KeyI", "Enter", "KeyJ", "KeyL", "KeyK"],
+            _ => return None,
+        };
+        let mut bindings = KeyBindings {
+            bindings: HashMap::new(),
+        };
+        for (action, code) in [
+            Action::Accelerate,
+            Action::Brake,
+            Action::SteerLeft,
+            Action::SteerRight,
+            Action::Straighten,
+        ]
+        .into_iter()
+        .zip(driving)
+        {
+            bindings.bind(action, &[code]);
+        }
+        bindings.bind_menu_keys();
+        bindings.bind(Action::Confirm, &["Space", "Enter"]);
+        bindings.bind(Action::Pause, &["Escape", "KeyP"]);
+        bindings.bind(Action::WatchReplay, &["KeyR"]);
+        bindings.bind(Action::NextTheme, &["KeyT"]);
+        bindings.bind(Action::Mute, &["KeyM"]);
+        Some(bindings)
+    }
+
+    /// Replaces the keys bound to `action`
+    pub fn bind(&mut self, action: Action, codes: &[&str]) {
+        self.bindings
+            .insert(action, codes.iter().map(|code| code.to_string()).collect());
+    }
+
+    ///
+    /// Binds a single key to an action, e.g. on the controls screen
+    ///
+    /// A key may only be shared the way every preset shares keys: Brake with
+    /// Confirm, and the menu arrows with Accelerate and Straighten. The menu
+    /// keys then follow the new driving keys.
+    ///
+    /// # Arguments
+    /// * `action` - Action to bind
+    /// * `code` - `KeyboardEvent.code` of the new key
+    ///
+    /// # Returns
+    /// The action already using the key if it cannot be shared, leaving the bindings unchanged
+    pub fn rebind(&mut self, action: Action, code: &str) -> std::result::Result<(), Action> {
+        for (menu, menu_key, driving) in [
+            (Action::MenuUp, MENU_UP_KEY, Action::Accelerate),
+            (Action::MenuDown, MENU_DOWN_KEY, Action::Straighten),
+        ] {
+            if code == menu_key && action != driving {
+                return Err(menu);
+            }
+        }
+        let owner = Action::iter().find(|owner| {
+            *owner != action
+                && !matches!(owner, Action::MenuUp | Action::MenuDown)
+                && !(*owner == Action::Confirm && action == Action::Brake)
+                && self.codes(*owner).iter().any(|bound| bound == code)
+        });
+        if let Some(owner) = owner {
+            return Err(owner);
+        }
+        self.bind(action, &[code]);
+        self.bind_menu_keys();
+        Ok(())
+    }
+
+    /// Binds the menu arrows along with the keys of Accelerate and Straighten
+    fn bind_menu_keys(&mut self) {
+        for (menu, menu_key, driving) in [
+            (Action::MenuUp, MENU_UP_KEY, Action::Accelerate),
+            (Action::MenuDown, MENU_DOWN_KEY, Action::Straighten),
+        ] {
+            let mut codes = vec![menu_key.to_string()];
+            for code in self.codes(driving) {
+                if !codes.contains(code) {
+                    codes.push(code.clone());
+                }
+            }
+            self.bindings.insert(menu, codes);
+        }
+    }
+
+    /// Gets the keys bound to `action`
+    pub fn codes(&self, action: Action) -> &[String] {
+        self.bindings
+            .get(&action)
+            .map_or(&[], |codes| codes.as_slice())
+    }
+
+    /// Holds the actions bound to the pressed keys
+    pub fn apply(&self, keystate: &KeyState, actions: &mut ActionState) {
+        for (action, codes) in self.bindings.iter() {
+            if codes.iter().any(|code| keystate.is_pressed(code)) {
+                actions.set_active(*action);
+            }
+        }
+    }
+
+    /// Encodes the bindings into their text form
+    pub fn encode(&self) -> String {
+        Action::iter()
+            .map(|action| format!("{}={}\n", action, self.codes(action).join(",")))
+            .collect()
+    }
+
+    /// Decodes bindings from their text form
+    pub fn decode(text: &str) -> Result<Self> {
+        let mut bindings = KeyBindings::default();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let (action, codes) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid key binding {:?}", line))?;
+            let action: Action = action
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("Unknown action {:?}", action))?;
+            let codes: Vec<&str> = codes
+                .split(',')
+                .map(str::trim)
+                .filter(|code| !code.is_empty())
+                .collect();
+            bindings.bind(action, &codes);
+        }
+        Ok(bindings)
+    }
 }
 
-#[derive(Clone)]
-pub struct Sound {
-    pub buffer: AudioBuffer,
+/// Keeps the key bindings that the keyboard maps keys with
+pub trait KeyBindingStore {
+    /// Bindings in use
+    fn key_bindings(&self) -> KeyBindings;
+    /// Change the bindings, which the backend keeps for the next session
+    fn set_key_bindings(&self, bindings: KeyBindings) -> Result<()>;
 }
 
-impl Audio {
-    pub fn new() -> Result<Self> {
-        Ok(Audio {
-            context: sound::create_audio_context()?,
-        })
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::{FileAssets, SilentAudio};
+
+    #[test]
+    fn key_bindings_decode_replaces_listed_actions_and_keeps_the_rest() {
+        let bindings = KeyBindings::decode("brake = KeyB, KeyN\n\nsteer_left=KeyA\n").unwrap();
+        assert_eq!(bindings.codes(Action::Brake), ["KeyB", "KeyN"]);
+        assert_eq!(bindings.codes(Action::SteerLeft), ["KeyA"]);
+        assert_eq!(bindings.codes(Action::Accelerate), ["ArrowUp"]);
     }
 
-    pub async fn load_sound(&self, filename: &str) -> Result<Sound> {
-        let array_buffer = browser::fetch_array_buffer(filename).await?;
+    #[test]
+    fn key_bindings_decode_unbinds_an_action_listed_without_keys() {
+        let bindings = KeyBindings::decode("mute=").unwrap();
+        assert!(bindings.codes(Action::Mute).is_empty());
+    }
 
-        let audio_buffer = sound::decode_audio_data(&self.context, &array_buffer).await?;
+    #[test]
+    fn key_bindings_decode_gives_back_the_encoded_bindings() {
+        let preset = KeyBindings::preset("wasd").unwrap();
+        let decoded = KeyBindings::decode(&preset.encode()).unwrap();
+        for action in Action::iter() {
+            assert_eq!(decoded.codes(action), preset.codes(action));
+        }
+    }
 
-        Ok(Sound {
-            buffer: audio_buffer,
-        })
+    #[test]
+    fn key_bindings_decode_rejects_malformed_lines() {
+        assert!(KeyBindings::decode("brake KeyB").is_err());
+        assert!(KeyBindings::decode("jump=Space").is_err());
     }
 
-    pub fn play_sound(&self, sound: &Sound) -> Result<()> {
-        sound::play_sound(&self.context, &sound.buffer, sound::Looping::No)
+    #[test]
+    fn key_bindings_hold_the_actions_of_pressed_keys() {
+        let bindings = KeyBindings::decode("brake=KeyB").unwrap();
+        let mut keystate = KeyState::new();
+        keystate.set_pressed("KeyB");
+        keystate.set_pressed("ArrowUp");
+        let mut actions = ActionState::new();
+        bindings.apply(&keystate, &mut actions);
+        assert!(actions.is_active(Action::Brake));
+        assert!(actions.is_active(Action::Accelerate));
+        assert!(!actions.is_active(Action::SteerLeft));
     }
 
-    pub fn play_looping_sound(&self, sound: &Sound) -> Result<()> {
-        sound::play_sound(&self.context, &sound.buffer, sound::Looping::Yes)
+    #[test]
+    fn key_bindings_rebind_refuses_a_key_another_action_uses() {
+        let mut bindings = KeyBindings::default();
+        assert_eq!(bindings.rebind(Action::Brake, "KeyP"), Err(Action::Pause));
+        assert_eq!(
+            bindings.rebind(Action::SteerLeft, "Space"),
+            Err(Action::Brake)
+        );
+        assert_eq!(
+            bindings.rebind(Action::SteerLeft, "KeyM"),
+            Err(Action::Mute)
+        );
+        assert_eq!(bindings.codes(Action::Brake), ["Space"]);
+        assert_eq!(bindings.codes(Action::SteerLeft), ["ArrowLeft"]);
+    }
+
+    #[test]
+    fn key_bindings_rebind_shares_keys_only_like_the_presets() {
+        let mut bindings = KeyBindings::preset("wasd").unwrap();
+        assert_eq!(bindings.rebind(Action::Brake, "Enter"), Ok(()));
+        assert_eq!(
+            bindings.rebind(Action::SteerLeft, "ArrowUp"),
+            Err(Action::MenuUp)
+        );
+        assert_eq!(bindings.rebind(Action::Accelerate, "ArrowUp"), Ok(()));
+        assert_eq!(bindings.codes(Action::MenuUp), ["ArrowUp"]);
+        assert_eq!(bindings.rebind(Action::Straighten, "KeyK"), Ok(()));
+        assert_eq!(bindings.codes(Action::MenuDown), ["ArrowDown", "KeyK"]);
+    }
+
+    #[test]
+    fn assets_load_counts_failed_optional_files_as_done() {
+        let files = [AssetFile {
+            id: "missing".to_string(),
+            kind: AssetKind::Track,
+            path: "missing.txt".to_string(),
+        }];
+        let (audio, loader) = (SilentAudio::default(), FileAssets::new("/nonexistent"));
+        for optional in [false, true] {
+            let progress = RefCell::default();
+            let loaded = Assets::load(&files, &audio, &loader, &progress, optional);
+            assert!(futures::executor::block_on(loaded).is_err());
+            let progress = progress.borrow();
+            assert_eq!(progress.fraction(), if optional { 1.0 } else { 0.0 });
+            assert_eq!(progress.errors().is_empty(), optional);
+        }
+    }
+
+    const SPRITE: &str = concat!(
+        "# A cart\n",
+        "font = 24px sans-serif\n",
+        "color.r = red\n",
+        "line_height = 18\n",
+        "anchor = 2, -4\n",
+        "[rows]\n",
+        "o❚o\n",
+        " | \n",
+        "\n",
+        "[colors]\n",
+        " r\n",
+    );
+
+    #[test]
+    fn sprite_parse_reads_settings_rows_and_colors() {
+        let sprite = Sprite::parse(SPRITE).unwrap();
+        assert_eq!(sprite.font(), "24px sans-serif");
+        // Trailing blank rows are dropped
+        assert_eq!(sprite.len(), 2);
+        let position = sprite.row_position(&Point::new(100.0, 200.0), 1);
+        assert_eq!((position.x, position.y), (102.0, 178.0));
+        assert_eq!(
+            sprite.runs(0, "white"),
+            [
+                ("o".to_string(), "white"),
+                ("❚".to_string(), "red"),
+                ("o".to_string(), "white")
+            ]
+        );
+        assert_eq!(sprite.runs(1, "white"), [(" | ".to_string(), "white")]);
+    }
+
+    #[test]
+    fn sprite_tinted_draws_every_glyph_in_one_color() {
+        let sprite = Sprite::parse(SPRITE).unwrap().tinted();
+        assert_eq!(sprite.runs(0, "gray"), [("o❚o".to_string(), "gray")]);
+    }
+
+    #[test]
+    fn sprite_parse_keeps_the_anchor_at_the_origin_by_default() {
+        let sprite = Sprite::parse("font = 10px serif\nline_height = 10\n[rows]\nx").unwrap();
+        let position = sprite.row_position(&Point::new(5.0, 5.0), 0);
+        assert_eq!((position.x, position.y), (5.0, 5.0));
+    }
+
+    #[test]
+    fn sprite_parse_rejects_incomplete_or_unknown_settings() {
+        for text in [
+            "font = 10px serif\nline_height = 10",
+            "line_height = 10\n[rows]\nx",
+            "font = 10px serif\n[rows]\nx",
+            "font = 10px serif\nline_height = ten\n[rows]\nx",
+            "font = 10px serif\nline_height = 10\nanchor = 1\n[rows]\nx",
+            "font = 10px serif\nline_height = 10\nscale = 2\n[rows]\nx",
+            "font = 10px serif\nline_height = 10\ncolor.ab = red\n[rows]\nx",
+            "font = 10px serif\nline_height = 10\n[rows]\nx\n[colors]\ng",
+        ] {
+            assert!(Sprite::parse(text).is_err(), "{:?} parsed", text);
+        }
+    }
+
+    #[test]
+    fn sprite_parse_reads_every_sprite_in_the_assets() {
+        let sprites = concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets/sprites");
+        for entry in std::fs::read_dir(sprites).unwrap() {
+            let path = entry.unwrap().path();
+            let text = std::fs::read_to_string(&path).unwrap();
+            assert!(
+                Sprite::parse(&text).is_ok(),
+                "{} does not parse",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn mixer_settings_decode_reads_volumes_and_mute() {
+        let mixer = MixerSettings::decode("music = 0.5\n\nmuted=true\n").unwrap();
+        assert_eq!(mixer.volume(Bus::Music), 0.5);
+        assert_eq!(mixer.volume(Bus::Master), 1.0);
+        assert_eq!(mixer.volume(Bus::Effects), 1.0);
+        assert!(mixer.muted);
+        assert_eq!(mixer.gain(Bus::Master), 0.0);
+        assert_eq!(mixer.gain(Bus::Music), 0.5);
+    }
+
+    #[test]
+    fn mixer_settings_decode_rounds_volumes_into_range() {
+        let mixer = MixerSettings::decode("master=1.7\nmusic=-0.2\neffects=0.33").unwrap();
+        assert_eq!(mixer.volume(Bus::Master), 1.0);
+        assert_eq!(mixer.volume(Bus::Music), 0.0);
+        assert!((mixer.volume(Bus::Effects) - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mixer_settings_decode_gives_back_the_encoded_settings() {
+        let mut mixer = MixerSettings::default();
+        mixer.set_volume(Bus::Effects, 0.4);
+        mixer.muted = true;
+        assert_eq!(MixerSettings::decode(&mixer.encode()).unwrap(), mixer);
+        assert_eq!(MixerSettings::decode("").unwrap(), MixerSettings::default());
+    }
+
+    #[test]
+    fn mixer_settings_decode_rejects_malformed_lines() {
+        for text in ["music 0.5", "voice=0.5", "music=loud", "muted=yes"] {
+            assert!(MixerSettings::decode(text).is_err(), "{:?} decoded", text);
+        }
     }
 }
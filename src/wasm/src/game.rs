@@ -1,26 +1,41 @@
 mod cart;
+mod ghost;
+mod menu;
 mod music;
 mod ornament;
+mod replay;
+mod scene;
+mod touch;
 mod wall;
 use crate::{
-    browser::now,
-    engine::{Audio, Game, KeyState, Line, Point, Renderer, Sound, Velocity},
-    game::wall::wall::WALLS_DATA,
+    engine::{
+        Action, ActionState, AssetKind, AssetLoader, Assets, Audio, Camera, FRAME_SIZE, Game,
+        KeyBindingStore, KeyBindings, Line, LoadProgress, Manifest, MixerSettings, Point, Renderer,
+        Sound, Sprite, THEMES, Theme, Velocity,
+    },
+    game::wall::wall::Track,
+    headless::SilentAudio,
 };
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use cart::cart::*;
+use ghost::ghost::*;
+use menu::menu::*;
 use music::music::*;
 use ornament::ornament::*;
+pub use replay::replay::Replay;
+use scene::scene::*;
+use std::{cell::RefCell, rc::Rc};
+pub use touch::touch::TouchControls;
 use wall::wall::*;
 /* <-- CONSTANT VALUE */
 
+/// Logical resolution of the game. The web canvas scales it to fit the window.
 pub const CANVAS_WIDTH: f32 = 800.0;
 pub const CANVAS_HEIGHT: f32 = 1000.0;
 const CART_START_X: f32 = CANVAS_WIDTH / 2.0;
 const CART_START_Y: f32 = 100.0;
 
-const FONT_COLOR: &str = "green";
 const STAGE_LEFT: f32 = 100.0;
 const STAGE_GOAL: f32 = 4200.0;
 const VELOCITY_X: f32 = 0.8;
@@ -42,32 +57,153 @@ const MESSAGE_TIME_X: f32 = 30.0;
 const MESSAGE_TIME_Y: f32 = 900.0;
 const MESSAGE_VELOCITY_X: f32 = 30.0;
 const MESSAGE_VELOCITY_Y: f32 = 860.0;
-const MESSAGE_TIME: i32 = 100;
+const MESSAGE_TARGET_X: f32 = 30.0;
+const MESSAGE_TARGET_Y: f32 = 820.0;
+/// Ticks the "Ready Go!" banner stays on screen
+const MESSAGE_TIME: u32 = 100;
 const MESSAGE_RUNNING: &str = "Ready Go!";
 const MESSAGE_GAMEOVER: &str = "Game Over!";
 const MESSAGE_GAMECLEAR: &str = "Congrantuation!!";
+const MESSAGE_REPLAY: &str = "Push R Key to watch the replay.";
+const MESSAGE_PLAYBACK: &str = "REPLAY";
+const MESSAGE_PAUSE: &str = "PAUSE";
+const MESSAGE_SOUND_UNAVAILABLE: &str = "Sound unavailable";
+const MESSAGE_MUTED: &str = "Muted  [ M ]";
+const MESSAGE_SOUND_X: f32 = CANVAS_WIDTH - 20.0;
+const MESSAGE_SOUND_Y: f32 = 975.0;
+/// Items of the pause menu, top to bottom
+const PAUSE_MENU: [&str; 3] = ["Resume", "Restart", "Quit to Title"];
+const PAUSE_MENU_Y: f32 = 540.0;
 const MESSAGE_DISTANCE: f32 = 120.0;
+const MESSAGE_THEME_Y: f32 = 140.0;
 const ORNAMENT_X: f32 = STAGE_LEFT + 20.0;
 const ORNAMENT_Y: f32 = 950.0;
 const ORNAMENT_WIDTH: f32 = 10.0;
 const ORNAMENT_HEIGHT: f32 = 9900.0;
-const BRAKESOUND_FILE: &str = "/cart/assets/beep-7.wav";
-const BACKGROUND_MUSIC_FILE: &str = "/cart/assets/background_song.mp3";
+/// Asset manifest, relative to the asset base URL
+const MANIFEST_FILE: &str = "assets/manifest.json";
+/// Ids of the assets in the manifest
+const BRAKESOUND: &str = "brake";
+const BACKGROUND_MUSIC: &str = "background_music";
+const TRACK: &str = "main";
+/// LOADING SCREEN
+const LOADING_BAR_X: f32 = 150.0;
+const LOADING_BAR_Y: f32 = 500.0;
+const LOADING_BAR_WIDTH: f32 = 500.0;
+const LOADING_BAR_HEIGHT: f32 = 30.0;
+const LOADING_ERROR_X: f32 = 40.0;
+const LOADING_ERROR_Y: f32 = 400.0;
+const LOADING_ERROR_DISTANCE: f32 = 30.0;
 
 /* CONSTANT VALUE --> */
 
+/// Sprites of the pieces and screens, loaded from the asset manifest
+pub struct Sprites {
+    cart: Sprite,
+    cart_knocked: Sprite,
+    tree: Sprite,
+    goal: Sprite,
+}
+impl Sprites {
+    fn new(assets: &Assets) -> Result<Self> {
+        Ok(Sprites {
+            cart: assets.sprite("cart")?,
+            cart_knocked: assets.sprite("cart_knocked")?,
+            tree: assets.sprite("tree")?,
+            goal: assets.sprite("goal")?,
+        })
+    }
+}
+
 pub struct GameStage {
     machine: Option<GameStageStateMachine>,
+    audio: Rc<dyn Audio>,
+    /// Loads the fonts and tracks
+    loader: Rc<dyn AssetLoader>,
+    /// Keys the player drives with, changed on the controls screen
+    key_bindings: Rc<dyn KeyBindingStore>,
+    /// Actions of the previous tick, to tell presses from held keys
+    previous: ActionState,
+    /// Menus shown on top of the race. The race runs while this is empty.
+    scenes: Vec<Scene>,
+    /// Colors the game is drawn with, picked on the title screen
+    theme: &'static Theme,
 }
 impl GameStage {
-    /// Create a new instance of the game stage
-    pub fn new() -> Self {
-        GameStage { machine: None }
+    /// Create a new instance of the game stage on the given platform backends
+    pub fn new(
+        audio: Rc<dyn Audio>,
+        loader: Rc<dyn AssetLoader>,
+        key_bindings: Rc<dyn KeyBindingStore>,
+    ) -> Self {
+        GameStage {
+            machine: None,
+            audio,
+            loader,
+            key_bindings,
+            previous: ActionState::new(),
+            scenes: vec![],
+            theme: &THEMES[0],
+        }
+    }
+    /// Get the result of the race once it reached GameOver or GameClear
+    pub fn outcome(&self) -> Option<Outcome> {
+        let (cleared, material) = match &self.machine {
+            Some(GameStageStateMachine::GameOver(state)) => (false, &state.material),
+            Some(GameStageStateMachine::GameClear(state)) => (true, &state.material),
+            _ => return None,
+        };
+        Some(Outcome {
+            cleared,
+            time: material.score,
+            position: material.cart.get_position(),
+            distance: material.distance,
+        })
+    }
+    /// Get the recorded input of the race once it reached GameOver or GameClear
+    pub fn replay(&self) -> Option<&Replay> {
+        match &self.machine {
+            Some(GameStageStateMachine::GameOver(state)) => Some(&state.material.replay),
+            Some(GameStageStateMachine::GameClear(state)) => Some(&state.material.replay),
+            _ => None,
+        }
+    }
+    /// Change the mixer volumes, which the audio backend keeps for the next session
+    fn set_mixer(&self, mixer: MixerSettings) {
+        if let Err(err) = self.audio.set_mixer(mixer) {
+            log!("Error saving mixer settings {:#?}", err);
+        }
+    }
+    /// Change the key bindings, which the keyboard uses from the next tick on
+    fn set_key_bindings(&self, bindings: KeyBindings) {
+        if let Err(err) = self.key_bindings.set_key_bindings(bindings) {
+            log!("Error saving key bindings {:#?}", err);
+        }
+    }
+    /// Restart the race driven by a recorded replay instead of key input
+    pub fn start_playback(&mut self, replay: Replay) {
+        self.scenes.clear();
+        if let Some(machine) = self.machine.take() {
+            self.machine.replace(machine.watch_replay(replay));
+        }
     }
 }
+
+/// Result of a finished race
+pub struct Outcome {
+    /// `true` when the cart reached the goal, `false` when it crashed
+    pub cleared: bool,
+    /// Race time in milliseconds
+    pub time: i32,
+    /// Cart position on the track when the race ended
+    pub position: Point,
+    /// Distance covered along the track
+    pub distance: f32,
+}
 enum GameStageStateMachine {
     Ready(GameStageState<Ready>),
     Playing(GameStageState<Playing>),
+    Paused(GameStageState<Paused>),
     GameOver(GameStageState<GameOver>),
     GameClear(GameStageState<GameClear>),
 }
@@ -76,22 +212,55 @@ impl GameStageStateMachine {
     fn new(material: Material) -> Self {
         GameStageStateMachine::Ready(GameStageState::new(material))
     }
-    /// Update game state based on key input
-    fn update(self, _keystate: &KeyState) -> Self {
+    /// Update game state based on player actions
+    fn update(self, _actions: &ActionState) -> Self {
         match self {
-            GameStageStateMachine::Ready(state) => state.update(_keystate).into(),
-            GameStageStateMachine::Playing(state) => state.update(_keystate).into(),
-            GameStageStateMachine::GameOver(state) => state.update(_keystate).into(),
-            GameStageStateMachine::GameClear(state) => state.update(_keystate).into(),
+            // The title menu starts the race, see `start`
+            GameStageStateMachine::Ready(state) => state.into(),
+            GameStageStateMachine::Playing(state) => state.update(_actions).into(),
+            GameStageStateMachine::Paused(state) => state.update(_actions).into(),
+            GameStageStateMachine::GameOver(state) => state.update(_actions).into(),
+            GameStageStateMachine::GameClear(state) => state.update(_actions).into(),
+        }
+    }
+    /// Restart the race playing back a replay, whatever the current state
+    fn watch_replay(self, replay: Replay) -> Self {
+        match self {
+            GameStageStateMachine::Ready(state) => state.watch_replay(replay).into(),
+            GameStageStateMachine::Playing(state) => state.watch_replay(replay).into(),
+            GameStageStateMachine::Paused(state) => state.watch_replay(replay).into(),
+            GameStageStateMachine::GameOver(state) => state.watch_replay(replay).into(),
+            GameStageStateMachine::GameClear(state) => state.watch_replay(replay).into(),
+        }
+    }
+    /// Start a race from the Ready state, against the clock of the best run for a time trial
+    fn start(self, time_trial: bool) -> Self {
+        match self {
+            GameStageStateMachine::Ready(state) => state.start_running(time_trial).into(),
+            _ => self,
+        }
+    }
+    /// Pause the race if one is running
+    fn pause(self) -> Self {
+        match self {
+            GameStageStateMachine::Playing(state) => state.pause().into(),
+            _ => self,
         }
     }
     /// Draw game elements based on current state
-    fn draw(&self, renderer: &Renderer) {
+    fn draw(&self, renderer: &dyn Renderer, theme: &Theme) {
         match self {
-            GameStageStateMachine::Ready(state) => state.material.draw(renderer),
-            GameStageStateMachine::Playing(state) => state.material.draw(renderer),
-            GameStageStateMachine::GameOver(state) => state.material.draw(renderer),
-            GameStageStateMachine::GameClear(state) => state.material.draw(renderer),
+            GameStageStateMachine::Ready(state) => state.material.draw(renderer, theme),
+            GameStageStateMachine::Playing(state) => {
+                state.material.draw(renderer, theme);
+                state.material.draw_ghost(renderer, theme);
+            }
+            GameStageStateMachine::Paused(state) => {
+                state.material.draw(renderer, theme);
+                state.material.draw_ghost(renderer, theme);
+            }
+            GameStageStateMachine::GameOver(state) => state.material.draw(renderer, theme),
+            GameStageStateMachine::GameClear(state) => state.material.draw(renderer, theme),
         };
     }
 }
@@ -105,6 +274,11 @@ impl From<GameStageState<Playing>> for GameStageStateMachine {
         GameStageStateMachine::Playing(state)
     }
 }
+impl From<GameStageState<Paused>> for GameStageStateMachine {
+    fn from(state: GameStageState<Paused>) -> Self {
+        GameStageStateMachine::Paused(state)
+    }
+}
 impl From<GameStageState<GameOver>> for GameStageStateMachine {
     fn from(state: GameStageState<GameOver>) -> Self {
         GameStageStateMachine::GameOver(state)
@@ -120,40 +294,39 @@ struct GameStageState<T> {
     _state: T,
     material: Material,
 }
+impl<T> GameStageState<T> {
+    /// Start the race over from fresh materials, playing back the replay with
+    /// the background music, which a crash or a pause may have stopped
+    fn watch_replay(self, replay: Replay) -> GameStageState<Playing> {
+        let mut material = Material::reset(self.material);
+        material.playback = Some(replay);
+        material.music.resume();
+        material.music.start_background();
+        GameStageState {
+            _state: Playing,
+            material,
+        }
+    }
+}
 
 struct Ready;
 impl GameStageState<Ready> {
-    /// Create new game state in Ready state
-    fn new(material: Material) -> GameStageState<Ready> {
+    /// Create new game state in Ready state, with the background music playing
+    /// and the engine of a race left from the pause menu stopped
+    fn new(mut material: Material) -> GameStageState<Ready> {
+        material.music.stop_engine();
+        material.music.start_background();
         GameStageState {
             _state: Ready,
             material,
         }
     }
     /// Start game and transition to Playing state
-    fn start_running(self) -> GameStageState<Playing> {
+    fn start_running(mut self, time_trial: bool) -> GameStageState<Playing> {
+        self.material.time_trial = time_trial;
         GameStageState {
             _state: Playing,
-            material: self.material,
-        }
-    }
-    /// Handle key input in Ready state (start game with space key)
-    fn update(self, _keystate: &KeyState) -> ReadyEndState {
-        if _keystate.is_pressed("Space") {
-            return ReadyEndState::Complete(self.start_running());
-        }
-        ReadyEndState::Continue(self)
-    }
-}
-enum ReadyEndState {
-    Complete(GameStageState<Playing>),
-    Continue(GameStageState<Ready>),
-}
-impl From<ReadyEndState> for GameStageStateMachine {
-    fn from(state: ReadyEndState) -> Self {
-        match state {
-            ReadyEndState::Complete(running) => running.into(),
-            ReadyEndState::Continue(ready) => ready.into(),
+            material: Material::reset(self.material),
         }
     }
 }
@@ -161,95 +334,72 @@ impl From<ReadyEndState> for GameStageStateMachine {
 struct Playing;
 impl GameStageState<Playing> {
     /// Main update process during gameplay
-    fn update(mut self, _keystate: &KeyState) -> RunningEndState {
+    fn update(mut self, _actions: &ActionState) -> RunningEndState {
+        // Pausing reads live input, even while watching a replay
+        if _actions.is_pressed(Action::Pause) {
+            return RunningEndState::Paused(self.pause());
+        }
+        // Replace live input with the recorded actions while watching a replay
+        let _playback_actions;
+        let _actions = match &self.material.playback {
+            Some(replay) => {
+                _playback_actions = replay.actions(self.material.replay.len());
+                &_playback_actions
+            }
+            None => _actions,
+        };
+        self.material.replay.record(_actions);
+
         // Check if cart completed one lap
         let _position: Point = self.material.cart.get_position();
         let mut _velocity: Velocity = self.material.cart.get_velocity();
+        self.material.ticks += 1;
         self.material.distance += _velocity.y;
 
         if self.material.distance > STAGE_GOAL {
-            // Reset walls to original positions
-            self.material.walls.clear();
-            for w in WALLS_DATA {
-                self.material.walls.push(Wall::new(
-                    Point { x: w.0, y: w.1 },
-                    Point { x: w.2, y: w.3 },
-                    Velocity { x: 0.0, y: 0.0 },
-                ));
-            }
-
-            // Reset ornaments to original positions
-            self.material.ornaments = vec![Ornament::new(
-                Point {
-                    x: ORNAMENT_X,
-                    y: ORNAMENT_Y,
-                },
-                Point {
-                    x: ORNAMENT_X + ORNAMENT_WIDTH,
-                    y: ORNAMENT_Y + ORNAMENT_HEIGHT,
-                },
-                Velocity { x: 0.0, y: 0.0 },
-            )];
-            let mut _highscore: i32 = now().unwrap() as i32 - self.material.start_time;
+            let mut _highscore: i32 = race_time(self.material.ticks);
             if self.material.highscore != 0 {
                 _highscore = _highscore.min(self.material.highscore);
             }
+            // Keep the run that set the best time for the ghost
+            if _highscore != self.material.highscore {
+                self.material.best_replay = Some(self.material.replay.clone());
+            }
             self.material.highscore = _highscore;
-            self.material.score = now().unwrap() as i32 - self.material.start_time;
+            self.material.score = race_time(self.material.ticks);
+            self.material.music.stop_effects();
+            self.material.music.stop_engine();
             return RunningEndState::GameClear(GameStageState {
                 _state: GameClear,
                 material: self.material,
             });
         }
-        if _keystate.is_pressed("ArrowUp") && _velocity.y < VELOCITY_LIMIT {
-            _velocity.y += VELOCITY_STEP;
-        }
-        if _keystate.is_pressed("ArrowDown") {
-            _velocity.x = 0.0;
-        }
-        if _keystate.is_pressed("ArrowLeft") {
-            _velocity.x = -VELOCITY_X;
-        }
-        if _keystate.is_pressed("ArrowRight") {
-            _velocity.x = VELOCITY_X;
-        }
-        if _keystate.is_pressed("Space") {
-            _velocity.y -= VELOCITY_BRAKE_STEP;
-            self.material.music.clone().play_brake_sound();
-        }
-
-        // velocity limit
-        if _velocity.y < VELOCITY_ZERO {
-            _velocity.y = 0.0
-        }
+        _velocity = steer(_velocity, _actions);
+        self.material.music.update(
+            _actions.is_active(Action::Brake),
+            _velocity.y / VELOCITY_LIMIT,
+            stereo_pan(_position.x),
+        );
         self.material.cart.run(_velocity);
 
-        // Ornament
-        self.material.ornaments.iter_mut().for_each(|ornament| {
-            ornament.run(Velocity {
-                x: 0.0,
-                y: _velocity.y,
-            });
-        });
-
-        // walls update
-        self.material.walls.iter_mut().for_each(|wall| {
-            wall.run(Velocity {
-                x: 0.0,
-                y: _velocity.y,
-            });
-        });
+        if let Some(ghost) = self.material.ghost.as_mut() {
+            ghost.update();
+        }
 
         // Check Cart for Walls
         let _knocked = false;
-        for i in 0..self.material.walls.len() {
-            let _wall = &self.material.walls[i];
-            let _line = _wall.get_line();
-            if _wall.p().y.min(_wall.q().y) > self.material.distance {
-                continue;
-            }
-            if self.material.cart.intersect(_line) {
+        // Only walls within the cart's reach this tick can touch it
+        let _reach = self.material.cart.reach(_position);
+        for i in self.material.track.walls_near(&_reach) {
+            let _line = self.material.walls[i].get_line();
+            if self.material.cart.intersect(_position, _line) {
                 self.material.cart.knocked();
+                self.material.music.stop_background();
+                self.material
+                    .music
+                    .play_crash_sound(stereo_pan(self.material.cart.get_position().x));
+                self.material.music.stop_engine();
+                self.material.score = race_time(self.material.ticks);
                 return RunningEndState::GameOver(GameStageState {
                     _state: GameOver,
                     material: self.material,
@@ -258,46 +408,112 @@ impl GameStageState<Playing> {
         }
 
         self.material.cart.update();
-        self.material.ornaments.iter_mut().for_each(|ornament| {
-            ornament.update();
-        });
-        self.material.walls.iter_mut().for_each(|wall| {
-            wall.update();
-        });
+        // The track fits the screen horizontally, so only follow the cart along it
+        let _position = self.material.cart.get_position();
+        self.material
+            .camera
+            .follow(&_position, &Point::new(_position.x, CART_START_Y));
 
         RunningEndState::Continue(self)
     }
+    /// Freeze the race and its music, and open the pause menu
+    fn pause(mut self) -> GameStageState<Paused> {
+        self.material.music.pause();
+        GameStageState {
+            _state: Paused {
+                menu: Menu::new(PAUSE_MENU.len()),
+            },
+            material: self.material,
+        }
+    }
 }
 impl From<RunningEndState> for GameStageStateMachine {
     fn from(state: RunningEndState) -> Self {
         match state {
             RunningEndState::Continue(running) => running.into(),
+            RunningEndState::Paused(paused) => paused.into(),
             RunningEndState::GameOver(gameover) => gameover.into(),
             RunningEndState::GameClear(gameclear) => gameclear.into(),
         }
     }
 }
 
+/// Race on hold, with a menu of the `PAUSE_MENU` items
+struct Paused {
+    menu: Menu,
+}
+impl GameStageState<Paused> {
+    /// Handle the pause menu (move with MenuUp/MenuDown, pick with Confirm,
+    /// resume with Pause)
+    fn update(mut self, _actions: &ActionState) -> PausedEndState {
+        if _actions.is_pressed(Action::Pause) {
+            return PausedEndState::Resume(self.resume());
+        }
+        match self._state.menu.update(_actions) {
+            Some(0) => PausedEndState::Resume(self.resume()),
+            Some(1) => PausedEndState::Resume(self.restart()),
+            Some(_) => PausedEndState::Quit(self.quit()),
+            None => PausedEndState::Continue(self),
+        }
+    }
+    /// Carry on with the race where it was paused
+    fn resume(self) -> GameStageState<Playing> {
+        self.material.music.resume();
+        GameStageState {
+            _state: Playing,
+            material: self.material,
+        }
+    }
+    /// Start the race over from fresh materials
+    fn restart(self) -> GameStageState<Playing> {
+        self.material.music.resume();
+        GameStageState {
+            _state: Playing,
+            material: Material::reset(self.material),
+        }
+    }
+    /// Give up the race and go back to the title screen
+    fn quit(self) -> GameStageState<Ready> {
+        self.material.music.resume();
+        GameStageState::new(Material::reset(self.material))
+    }
+}
+enum PausedEndState {
+    Continue(GameStageState<Paused>),
+    Resume(GameStageState<Playing>),
+    Quit(GameStageState<Ready>),
+}
+impl From<PausedEndState> for GameStageStateMachine {
+    fn from(state: PausedEndState) -> Self {
+        match state {
+            PausedEndState::Continue(paused) => paused.into(),
+            PausedEndState::Resume(playing) => playing.into(),
+            PausedEndState::Quit(ready) => ready.into(),
+        }
+    }
+}
+
 struct GameOver;
 impl GameStageState<GameOver> {
-    /// Handle GameOver state (restart with space key)
-    fn update(self, _keystate: &KeyState) -> GameOverEndState {
-        if _keystate.is_pressed("Space") {
+    /// Handle GameOver state (restart with Confirm, watch the replay with WatchReplay).
+    fn update(self, _actions: &ActionState) -> GameOverEndState {
+        if _actions.is_pressed(Action::Confirm) {
             GameOverEndState::Complete(self.new_game())
+        } else if _actions.is_active(Action::WatchReplay) {
+            let replay = self.material.replay.clone();
+            GameOverEndState::Replay(self.watch_replay(replay))
         } else {
             GameOverEndState::Continue(self)
         }
     }
     /// Start new game (reset materials to Ready state)
     fn new_game(self) -> GameStageState<Ready> {
-        GameStageState {
-            _state: Ready,
-            material: Material::reset(self.material),
-        }
+        GameStageState::new(Material::reset(self.material))
     }
 }
 enum RunningEndState {
     Continue(GameStageState<Playing>),
+    Paused(GameStageState<Paused>),
     GameOver(GameStageState<GameOver>),
     GameClear(GameStageState<GameClear>),
 }
@@ -305,42 +521,46 @@ enum RunningEndState {
 enum GameOverEndState {
     Continue(GameStageState<GameOver>),
     Complete(GameStageState<Ready>),
+    Replay(GameStageState<Playing>),
 }
 impl From<GameOverEndState> for GameStageStateMachine {
     fn from(state: GameOverEndState) -> Self {
         match state {
             GameOverEndState::Continue(game_over) => game_over.into(),
             GameOverEndState::Complete(ready) => ready.into(),
+            GameOverEndState::Replay(playing) => playing.into(),
         }
     }
 }
 struct GameClear;
 impl GameStageState<GameClear> {
-    /// Handle GameClear state (restart with space key)
-    fn update(self, _keystate: &KeyState) -> GameClearEndState {
-        if _keystate.is_pressed("Space") {
+    /// Handle GameClear state (restart with Confirm, watch the replay with WatchReplay).
+    fn update(self, _actions: &ActionState) -> GameClearEndState {
+        if _actions.is_pressed(Action::Confirm) {
             GameClearEndState::Complete(self.new_game())
+        } else if _actions.is_active(Action::WatchReplay) {
+            let replay = self.material.replay.clone();
+            GameClearEndState::Replay(self.watch_replay(replay))
         } else {
             GameClearEndState::Continue(self)
         }
     }
     /// Start new game (reset materials to Ready state)
     fn new_game(self) -> GameStageState<Ready> {
-        GameStageState {
-            _state: Ready,
-            material: Material::reset(self.material),
-        }
+        GameStageState::new(Material::reset(self.material))
     }
 }
 enum GameClearEndState {
     Continue(GameStageState<GameClear>),
     Complete(GameStageState<Ready>),
+    Replay(GameStageState<Playing>),
 }
 impl From<GameClearEndState> for GameStageStateMachine {
     fn from(state: GameClearEndState) -> Self {
         match state {
             GameClearEndState::Continue(game_clear) => game_clear.into(),
             GameClearEndState::Complete(ready) => ready.into(),
+            GameClearEndState::Replay(playing) => playing.into(),
         }
     }
 }
@@ -393,11 +613,7 @@ impl State<Running> {
     /// Create new Running state
     pub fn new(p: Point, q: Point, velocity: Velocity) -> Self {
         State {
-            context: Context {
-                p: p,
-                q: q,
-                velocity: velocity,
-            },
+            context: Context { p, q, velocity },
             _state: Running {},
         }
     }
@@ -468,7 +684,7 @@ pub trait Piece {
             ),
         )
     }
-    fn draw(&self, renderer: &Renderer);
+    fn draw(&self, renderer: &dyn Renderer, theme: &Theme, sprites: &Sprites);
     fn p(&self) -> Point {
         self.get_state_machine().context().p
     }
@@ -479,31 +695,55 @@ pub trait Piece {
 
 pub struct Material {
     music: Music,
-    start_time: i32,
+    /// Simulation ticks since the race started
+    ticks: u32,
     distance: f32,
     highscore: i32,
     score: i32,
+    /// Keys held on each tick of the current race
+    replay: Replay,
+    /// Replay of the run that set the best time
+    best_replay: Option<Replay>,
+    /// Ghost cart following `best_replay`
+    ghost: Option<Ghost>,
+    /// Replay driving the race instead of key input
+    playback: Option<Replay>,
+    /// View of the track that follows the cart
+    camera: Camera,
+    /// Race against the clock, with the time left to beat the best time shown
+    time_trial: bool,
+    /// Course the walls are built from
+    track: Rc<Track>,
+    /// Figures the pieces are drawn with
+    sprites: Rc<Sprites>,
     cart: Cart,
     ornaments: Vec<Ornament>,
     walls: Vec<Wall>,
 }
 impl Material {
-    fn new(_highscore: i32, audio: Audio, sound: Sound) -> Self {
-        let mut _walls = vec![];
-        let _start_time: i32 = now().unwrap() as i32;
-        for w in WALLS_DATA {
-            _walls.push(Wall::new(
-                Point { x: w.0, y: w.1 },
-                Point { x: w.2, y: w.3 },
-                Velocity { x: 0.0, y: 0.0 },
-            ));
-        }
+    fn new(
+        _highscore: i32,
+        best_replay: Option<Replay>,
+        time_trial: bool,
+        music: Music,
+        track: Rc<Track>,
+        sprites: Rc<Sprites>,
+    ) -> Self {
         Material {
-            music: Music::new(audio, sound),
+            music,
             distance: 0.0,
-            start_time: _start_time,
+            ticks: 0,
             highscore: _highscore,
             score: 0,
+            replay: Replay::default(),
+            ghost: best_replay.clone().map(Ghost::new),
+            best_replay,
+            playback: None,
+            camera: Camera::default(),
+            time_trial,
+            walls: track.walls(),
+            track,
+            sprites,
             cart: Cart::new(
                 Point {
                     x: CART_START_X,
@@ -522,106 +762,219 @@ impl Material {
                 },
                 Velocity { x: 0.0, y: 0.0 },
             )],
-            walls: _walls,
         }
     }
-    /// Reset game materials (keep highscore)
+    /// Reset game materials (keep highscore and race mode)
     fn reset(material: Self) -> Self {
         Material::new(
             material.highscore,
-            material.music.audio.clone(),
-            material.music.sound.clone(),
+            material.best_replay,
+            material.time_trial,
+            material.music,
+            material.track,
+            material.sprites,
         )
     }
-    /// Draw all game elements
-    fn draw(&self, renderer: &Renderer) {
-        self.cart.draw(renderer);
+    /// Draw the ghost cart of the best run, if there is one
+    fn draw_ghost(&self, renderer: &dyn Renderer, theme: &Theme) {
+        if let Some(ghost) = &self.ghost {
+            ghost.draw(&self.camera.view(renderer), theme, &self.sprites);
+        }
+    }
+    /// Draw the game elements the camera can see
+    fn draw(&self, renderer: &dyn Renderer, theme: &Theme) {
+        let _view = self.camera.visible_area(CANVAS_WIDTH, CANVAS_HEIGHT);
+        let renderer = self.camera.view(renderer);
+        self.cart.draw(&renderer, theme, &self.sprites);
         self.ornaments.iter().for_each(|ornament| {
-            ornament.draw(renderer);
-        });
-        self.walls.iter().for_each(|wall| {
-            wall.draw(renderer);
+            ornament.draw_visible(&renderer, theme, &self.sprites, &_view);
         });
+        for i in self.track.walls_near(&_view) {
+            self.walls[i].draw(&renderer, theme, &self.sprites);
+        }
+    }
+}
+
+impl GameStage {
+    ///
+    /// Load the assets and set up game materials in the Ready state
+    ///
+    /// # Arguments
+    /// * `progress` - Loading state shown on the loading screen
+    ///
+    /// # Returns
+    /// The game stage showing the title menu, or an error if an asset failed
+    pub async fn load(&self, progress: &RefCell<LoadProgress>) -> Result<GameStage> {
+        log!("START");
+        let audio = self.audio.clone();
+        let manifest = self
+            .loader
+            .load_text(&self.loader.locate(MANIFEST_FILE))
+            .await
+            .and_then(|json| Manifest::parse(&json))
+            .map_err(|err| anyhow!("Could not load {}: {:#}", MANIFEST_FILE, err))?;
+        // Sound is optional, so the sounds are loaded apart from the rest
+        let (sound_files, files): (Vec<_>, Vec<_>) = manifest
+            .files()
+            .into_iter()
+            .partition(|file| matches!(file.kind, AssetKind::Sound | AssetKind::Music));
+        let (assets, sounds) = futures::join!(
+            Assets::load(
+                &files,
+                audio.as_ref(),
+                self.loader.as_ref(),
+                progress,
+                false
+            ),
+            Assets::load(
+                &sound_files,
+                audio.as_ref(),
+                self.loader.as_ref(),
+                progress,
+                true
+            ),
+        );
+        let assets = assets?;
+        let (audio, sounds) = match sounds {
+            Ok(sounds) => (audio, sounds),
+            Err(err) => {
+                log!("Sound unavailable, playing without it: {:#}", err);
+                let silent: Rc<dyn Audio> = Rc::new(SilentAudio::default());
+                let sounds = Assets::load(
+                    &sound_files,
+                    silent.as_ref(),
+                    self.loader.as_ref(),
+                    &RefCell::default(),
+                    true,
+                )
+                .await?;
+                (silent, sounds)
+            }
+        };
+        let sprites = Sprites::new(&assets)?;
+        let track = Track::parse(assets.track(TRACK)?)
+            .map_err(|err| anyhow!("Invalid track file: {:#}", err))?;
+
+        let machine = GameStageStateMachine::new(Material::new(
+            0,
+            None,
+            false,
+            Music::new(
+                audio.clone(),
+                sounds.sound(BRAKESOUND)?,
+                sounds.sound(BACKGROUND_MUSIC)?,
+            ),
+            Rc::new(track),
+            Rc::new(sprites),
+        ));
+        Ok(GameStage {
+            machine: Some(machine),
+            audio,
+            loader: self.loader.clone(),
+            key_bindings: self.key_bindings.clone(),
+            previous: ActionState::new(),
+            scenes: vec![Scene::title()],
+            theme: self.theme,
+        })
     }
 }
 
 #[async_trait(?Send)]
 impl Game for GameStage {
     /// Initialize game and set up audio and game materials
-    async fn initialize(&self) -> Result<Box<dyn Game>> {
-        log!("START");
-        match &self.machine {
-            _none => {
-                let audio = Audio::new()?;
-                let sound = audio.load_sound(BRAKESOUND_FILE).await?;
-                let background_music = audio.load_sound(BACKGROUND_MUSIC_FILE).await?;
-                audio.play_looping_sound(&background_music)?;
-
-                let mut _walls = vec![];
-                for w in WALLS_DATA {
-                    _walls.push(Wall::new(
-                        Point { x: w.0, y: w.1 },
-                        Point { x: w.2, y: w.3 },
-                        Velocity { x: 0.0, y: 0.0 },
-                    ));
-                }
-                let machine = GameStageStateMachine::new(Material {
-                    start_time: 0,
-                    distance: 0.0,
-                    highscore: 0,
-                    score: 0,
-                    music: Music::new(audio, sound),
-                    cart: Cart::new(
-                        Point {
-                            x: CART_START_X,
-                            y: CART_START_Y,
-                        },
-                        Velocity { x: 0.0, y: 0.0 },
-                    ),
-                    ornaments: vec![Ornament::new(
-                        Point {
-                            x: ORNAMENT_X,
-                            y: ORNAMENT_Y,
-                        },
-                        Point {
-                            x: ORNAMENT_X + ORNAMENT_WIDTH,
-                            y: ORNAMENT_Y + ORNAMENT_HEIGHT,
-                        },
-                        Velocity { x: 0.0, y: 0.0 },
-                    )],
-                    walls: _walls,
-                });
-                Ok(Box::new(GameStage {
-                    machine: Some(machine),
-                }))
-            }
-        }
+    async fn initialize(&self, progress: &RefCell<LoadProgress>) -> Result<Box<dyn Game>> {
+        Ok(Box::new(self.load(progress).await?))
     }
 
     /// Update entire game
-    fn update(&mut self, _keystate: &KeyState) {
-        if let Some(machine) = self.machine.take() {
-            self.machine.replace(machine.update(_keystate));
+    fn update(&mut self, _actions: &ActionState) {
+        let mut _actions = _actions.clone();
+        _actions.set_previous(&self.previous);
+        if _actions.is_pressed(Action::Mute) {
+            let mut mixer = self.audio.mixer();
+            mixer.muted = !mixer.muted;
+            self.set_mixer(mixer);
+        }
+        if let Some(scene) = self.scenes.last_mut() {
+            match scene.update(
+                &_actions,
+                &self.audio.mixer(),
+                &self.key_bindings.key_bindings(),
+            ) {
+                SceneEndState::Continue => {}
+                SceneEndState::Push(scene) => self.scenes.push(scene),
+                SceneEndState::Pop => {
+                    self.scenes.pop();
+                }
+                SceneEndState::Play { time_trial } => {
+                    self.scenes.clear();
+                    if let Some(machine) = self.machine.take() {
+                        self.machine.replace(machine.start(time_trial));
+                    }
+                }
+                SceneEndState::NextTheme => self.theme = self.theme.next(),
+                SceneEndState::Mixer(mixer) => self.set_mixer(mixer),
+                SceneEndState::KeyBindings(bindings) => self.set_key_bindings(bindings),
+            }
+        } else if let Some(machine) = self.machine.take() {
+            let machine = machine.update(&_actions);
+            // Back to the title menu whenever a race is over
+            if matches!(machine, GameStageStateMachine::Ready(_)) {
+                self.scenes.push(Scene::title());
+            }
+            self.machine.replace(machine);
         }
+        self.previous = _actions;
 
         //assert!(self.machine.is_some());
     }
     // Draw the entire game
-    fn draw(&self, renderer: &Renderer) {
-        renderer.clear(&Point { x: 0.0, y: 0.0 }, CANVAS_WIDTH, CANVAS_HEIGHT);
+    fn draw(&self, renderer: &dyn Renderer) {
+        let theme = self.theme();
+        renderer.clear(
+            &Point { x: 0.0, y: 0.0 },
+            CANVAS_WIDTH,
+            CANVAS_HEIGHT,
+            theme.background,
+        );
+        let _sound_message = if !self.audio.is_available() {
+            Some(MESSAGE_SOUND_UNAVAILABLE)
+        } else if self.audio.mixer().muted {
+            Some(MESSAGE_MUTED)
+        } else {
+            None
+        };
+        if let Some(_message) = _sound_message {
+            renderer.text(
+                &Point {
+                    x: MESSAGE_SOUND_X,
+                    y: MESSAGE_SOUND_Y,
+                },
+                _message,
+                theme.hud,
+                "20px selif",
+                "right",
+            );
+        }
+        if let Some(scene) = self.scenes.last() {
+            scene.draw(
+                renderer,
+                theme,
+                &self.audio.mixer(),
+                &self.key_bindings.key_bindings(),
+            );
+            return;
+        }
         match &self.machine {
-            Some(GameStageStateMachine::Ready(_state)) => {
-                draw_openning(renderer);
-            }
             Some(GameStageStateMachine::Playing(_state)) => {
-                let _time = now().unwrap() as i32 - _state.material.start_time;
+                let _time = race_time(_state.material.ticks);
                 renderer.text(
                     &Point {
                         x: MESSAGE_HIGHSCORE_X,
                         y: MESSAGE_HIGHSCORE_Y,
                     },
                     format!("BEST TIME: {}", get_passed_time(&_state.material.highscore)).as_str(),
-                    FONT_COLOR,
+                    theme.hud,
                     "32px myfont",
                     "left",
                 );
@@ -631,7 +984,7 @@ impl Game for GameStage {
                         y: MESSAGE_TIME_Y,
                     },
                     format!("Time: {}", get_passed_time(&_time)).as_str(),
-                    FONT_COLOR,
+                    theme.hud,
                     "28px selif",
                     "left",
                 );
@@ -641,27 +994,57 @@ impl Game for GameStage {
                         y: MESSAGE_VELOCITY_Y,
                     },
                     format!("Velocity: {:.1}", _state.material.cart.get_velocity().y).as_str(),
-                    FONT_COLOR,
+                    theme.hud,
                     "28px selif",
                     "left",
                 );
-                if _time < MESSAGE_TIME {
+                if _state.material.time_trial && _state.material.highscore != 0 {
+                    let _left = _state.material.highscore - _time;
+                    renderer.text(
+                        &Point {
+                            x: MESSAGE_TARGET_X,
+                            y: MESSAGE_TARGET_Y,
+                        },
+                        &if _left > 0 {
+                            format!("To beat: {}", get_passed_time(&_left))
+                        } else {
+                            "To beat: --.--".to_string()
+                        },
+                        theme.hud,
+                        "28px selif",
+                        "left",
+                    );
+                }
+                if _state.material.playback.is_some() {
+                    renderer.text(
+                        &Point {
+                            x: TITLE_MESSAGE_X,
+                            y: TITLE_MESSAGE_Y,
+                        },
+                        MESSAGE_PLAYBACK,
+                        theme.hud,
+                        "32px myfont",
+                        "center",
+                    );
+                } else if _state.material.ticks < MESSAGE_TIME {
                     renderer.text(
                         &Point {
                             x: TITLE_MESSAGE_X,
                             y: TITLE_MESSAGE_Y,
                         },
                         MESSAGE_RUNNING,
-                        FONT_COLOR,
+                        theme.hud,
                         "32px myfont",
                         "center",
                     );
                 }
             }
+            Some(GameStageStateMachine::Paused(_state)) => {
+                draw_pause_menu(renderer, theme, &_state._state.menu);
+            }
             Some(GameStageStateMachine::GameOver(_state)) => {
-                let _score = now().unwrap() as i32 - _state.material.start_time;
-                let mut _message = MESSAGE_GAMEOVER.to_string();
-                draw_gameover(renderer);
+                draw_gameover(renderer, theme);
+                draw_replay_message(renderer, theme);
             }
             Some(GameStageStateMachine::GameClear(_state)) => {
                 renderer.text(
@@ -670,7 +1053,7 @@ impl Game for GameStage {
                         y: TITLE_MESSAGE_Y,
                     },
                     MESSAGE_GAMECLEAR,
-                    FONT_COLOR,
+                    theme.hud,
                     "48px myfont",
                     "center",
                 );
@@ -681,17 +1064,134 @@ impl Game for GameStage {
                         y: TITLE_MESSAGE_Y - MESSAGE_DISTANCE,
                     },
                     &_message,
-                    FONT_COLOR,
+                    theme.hud,
                     "32px my_font",
                     "center",
                 );
+                draw_replay_message(renderer, theme);
             }
             _ => {}
         }
         if let Some(machine) = &self.machine {
-            machine.draw(renderer);
+            machine.draw(renderer, theme);
+        }
+    }
+    /// Colors of the theme picked on the title screen
+    fn theme(&self) -> &'static Theme {
+        self.theme
+    }
+    /// Pause the race and silence the music, e.g. when the page is hidden
+    fn pause(&mut self) {
+        if let Some(machine) = self.machine.take() {
+            self.machine.replace(machine.pause());
+        }
+        // Outside a race there is nothing to pause, but the music still plays
+        if let Err(err) = self.audio.suspend() {
+            log!("Error pausing audio {:#?}", err);
         }
     }
+    /// Bring the music back when the page is shown again, unless the race is paused
+    fn show(&mut self) {
+        if matches!(self.machine, Some(GameStageStateMachine::Paused(_))) {
+            return;
+        }
+        if let Err(err) = self.audio.resume() {
+            log!("Error resuming audio {:#?}", err);
+        }
+    }
+    /// Draw the progress bar while the assets load, and the assets that failed
+    fn draw_loading(&self, renderer: &dyn Renderer, progress: &LoadProgress) {
+        let theme = self.theme();
+        renderer.clear(
+            &Point { x: 0.0, y: 0.0 },
+            CANVAS_WIDTH,
+            CANVAS_HEIGHT,
+            theme.background,
+        );
+        let failed = !progress.errors().is_empty();
+        renderer.text(
+            &Point {
+                x: TITLE_MESSAGE_X,
+                y: TITLE_MESSAGE_Y,
+            },
+            if failed {
+                "Loading failed"
+            } else {
+                "Loading..."
+            },
+            theme.hud,
+            "48px selif",
+            "center",
+        );
+        // The fonts may still be loading, so the bar is drawn without text
+        let corners = [
+            Point::new(LOADING_BAR_X, LOADING_BAR_Y),
+            Point::new(LOADING_BAR_X + LOADING_BAR_WIDTH, LOADING_BAR_Y),
+            Point::new(
+                LOADING_BAR_X + LOADING_BAR_WIDTH,
+                LOADING_BAR_Y + LOADING_BAR_HEIGHT,
+            ),
+            Point::new(LOADING_BAR_X, LOADING_BAR_Y + LOADING_BAR_HEIGHT),
+        ];
+        for (index, corner) in corners.iter().enumerate() {
+            renderer.line(corner, &corners[(index + 1) % corners.len()], theme.hud);
+        }
+        renderer.clear(
+            &corners[0],
+            LOADING_BAR_WIDTH * progress.fraction(),
+            LOADING_BAR_HEIGHT,
+            theme.hud,
+        );
+        for (index, error) in progress.errors().iter().enumerate() {
+            renderer.text(
+                &Point {
+                    x: LOADING_ERROR_X,
+                    y: LOADING_ERROR_Y - LOADING_ERROR_DISTANCE * index as f32,
+                },
+                error,
+                theme.hud,
+                "18px selif",
+                "left",
+            );
+        }
+    }
+}
+
+/// Apply the held actions to a cart's velocity for one tick. Partially held
+/// actions (sticks and triggers) scale the step they apply.
+fn steer(mut _velocity: Velocity, _actions: &ActionState) -> Velocity {
+    if _actions.is_active(Action::Accelerate) && _velocity.y < VELOCITY_LIMIT {
+        _velocity.y += VELOCITY_STEP * _actions.value(Action::Accelerate);
+    }
+    if _actions.is_active(Action::Straighten) {
+        _velocity.x = 0.0;
+    }
+    if _actions.is_active(Action::SteerLeft) {
+        _velocity.x = -VELOCITY_X * _actions.value(Action::SteerLeft);
+    }
+    if _actions.is_active(Action::SteerRight) {
+        _velocity.x = VELOCITY_X * _actions.value(Action::SteerRight);
+    }
+    if _actions.is_active(Action::Brake) {
+        _velocity.y -= VELOCITY_BRAKE_STEP * _actions.value(Action::Brake);
+    }
+
+    // velocity limit
+    if _velocity.y < VELOCITY_ZERO {
+        _velocity.y = 0.0
+    }
+    _velocity
+}
+
+/// Stereo position of a sound made at `x` on screen, from the left edge at -1
+/// to the right edge at 1
+fn stereo_pan(x: f32) -> f32 {
+    (x / CANVAS_WIDTH * 2.0 - 1.0).clamp(-1.0, 1.0)
+}
+
+/// Convert simulation ticks to race time in milliseconds
+fn race_time(ticks: u32) -> i32 {
+    (ticks as f64 * FRAME_SIZE).round() as i32
 }
 
 fn get_passed_time(secondtime: &i32) -> String {
@@ -700,14 +1200,14 @@ fn get_passed_time(secondtime: &i32) -> String {
     format!("{:<02}.{:<02}", _seconds, _mini_seconds)
 }
 
-fn draw_gameover(renderer: &Renderer) {
+fn draw_gameover(renderer: &dyn Renderer, theme: &Theme) {
     renderer.text(
         &Point {
             x: TITLE_MESSAGE_X,
             y: TITLE_MESSAGE_Y,
         },
         MESSAGE_GAMEOVER,
-        FONT_COLOR,
+        theme.hud,
         "48px myfont",
         "center",
     );
@@ -717,110 +1217,170 @@ fn draw_gameover(renderer: &Renderer) {
             y: TITLE_MESSAGE_Y - MESSAGE_DISTANCE - 10.0,
         },
         TITLE_MESSAGE,
-        FONT_COLOR,
+        theme.hud,
         "48px myfont",
         "center",
     );
 }
-fn draw_openning(renderer: &Renderer) {
-    renderer.text(
-        &Point {
-            x: TITLE_X,
-            y: TITLE_Y,
-        },
-        TITLE,
-        FONT_COLOR,
-        "120px myfont",
-        "center",
-    );
+fn draw_pause_menu(renderer: &dyn Renderer, theme: &Theme, menu: &Menu) {
     renderer.text(
         &Point {
             x: TITLE_MESSAGE_X,
             y: TITLE_MESSAGE_Y,
         },
-        TITLE_MESSAGE,
-        FONT_COLOR,
+        MESSAGE_PAUSE,
+        theme.hud,
         "48px myfont",
         "center",
     );
+    menu.draw(renderer, &PAUSE_MENU, theme, PAUSE_MENU_Y);
+}
+fn draw_replay_message(renderer: &dyn Renderer, theme: &Theme) {
     renderer.text(
         &Point {
             x: TITLE_MESSAGE_X,
-            y: TITLE_MESSAGE_Y - MESSAGE_DISTANCE + 10.0,
-        },
-        "Speed Up",
-        FONT_COLOR,
-        "36px selif",
-        "center",
-    );
-    renderer.text(
-        &Point {
-            x: TITLE_MESSAGE_X,
-            y: TITLE_MESSAGE_Y - MESSAGE_DISTANCE - 30.0,
-        },
-        "▲",
-        FONT_COLOR,
-        "36px selif",
-        "center",
-    );
-    renderer.text(
-        &Point {
-            x: TITLE_MESSAGE_X - MESSAGE_DISTANCE,
-            y: TITLE_MESSAGE_Y - MESSAGE_DISTANCE - 80.0,
-        },
-        "To Left ◀",
-        FONT_COLOR,
-        "36px selif",
-        "center",
-    );
-    renderer.text(
-        &Point {
-            x: TITLE_MESSAGE_X + MESSAGE_DISTANCE + 5.0,
-            y: TITLE_MESSAGE_Y - MESSAGE_DISTANCE - 80.0,
-        },
-        "▶ To Right",
-        FONT_COLOR,
-        "36px selif",
-        "center",
-    );
-    renderer.text(
-        &Point {
-            x: TITLE_MESSAGE_X,
-            y: TITLE_MESSAGE_Y - MESSAGE_DISTANCE - 130.0,
-        },
-        "▼",
-        FONT_COLOR,
-        "36px selif",
-        "center",
-    );
-    renderer.text(
-        &Point {
-            x: TITLE_MESSAGE_X,
-            y: TITLE_MESSAGE_Y - MESSAGE_DISTANCE - 170.0,
-        },
-        "Straighten",
-        FONT_COLOR,
-        "36px selif",
-        "center",
-    );
-    renderer.text(
-        &Point {
-            x: TITLE_MESSAGE_X,
-            y: TITLE_MESSAGE_Y - MESSAGE_DISTANCE - 240.0,
-        },
-        "[   SPACE   ]",
-        FONT_COLOR,
-        "24 myfont",
-        "center",
-    );
-    renderer.text(
-        &Point {
-            x: TITLE_MESSAGE_X,
-            y: TITLE_MESSAGE_Y - MESSAGE_DISTANCE - 300.0,
+            y: TITLE_MESSAGE_Y - MESSAGE_DISTANCE * 2.0,
         },
-        "Brake",
-        FONT_COLOR,
-        "24 myfont",
+        MESSAGE_REPLAY,
+        theme.hud,
+        "28px selif",
         "center",
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::{FileAssets, MemoryKeyBindings};
+
+    /// Ticks a race held at full speed lasts at most, crash or goal
+    const RACE_TICKS: usize = 2000;
+
+    fn loaded_stage() -> GameStage {
+        let stage = GameStage::new(
+            Rc::new(SilentAudio::default()),
+            Rc::new(FileAssets::new(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../.."
+            ))),
+            Rc::new(MemoryKeyBindings::default()),
+        );
+        futures::executor::block_on(stage.load(&RefCell::default())).expect("assets load")
+    }
+
+    /// Runs one tick holding `held`
+    fn tick(stage: &mut GameStage, held: &[Action]) {
+        let mut actions = ActionState::new();
+        held.iter().for_each(|action| actions.set_active(*action));
+        stage.update(&actions);
+    }
+
+    fn start_race(stage: &mut GameStage) {
+        tick(stage, &[Action::Confirm]);
+        tick(stage, &[]);
+    }
+
+    /// Runs the race until it ends, holding `held` on every tick
+    fn race_to_the_end(stage: &mut GameStage, held: &[Action]) {
+        for _ in 0..RACE_TICKS {
+            if stage.outcome().is_some() {
+                return;
+            }
+            tick(stage, held);
+        }
+        panic!("race did not end in {} ticks", RACE_TICKS);
+    }
+
+    #[test]
+    fn play_on_the_title_menu_starts_a_race() {
+        let mut stage = loaded_stage();
+        assert!(matches!(stage.scenes.last(), Some(Scene::Title(_))));
+        assert!(matches!(
+            stage.machine,
+            Some(GameStageStateMachine::Ready(_))
+        ));
+
+        start_race(&mut stage);
+        assert!(stage.scenes.is_empty());
+        assert!(matches!(
+            stage.machine,
+            Some(GameStageStateMachine::Playing(_))
+        ));
+    }
+
+    #[test]
+    fn pause_holds_the_race_until_pressed_again() {
+        let mut stage = loaded_stage();
+        start_race(&mut stage);
+        tick(&mut stage, &[Action::Accelerate]);
+
+        tick(&mut stage, &[Action::Pause]);
+        assert!(matches!(
+            stage.machine,
+            Some(GameStageStateMachine::Paused(_))
+        ));
+        let Some(GameStageStateMachine::Paused(state)) = &stage.machine else {
+            unreachable!();
+        };
+        let ticks = state.material.ticks;
+        // Still held from the press that paused, so not a new press
+        tick(&mut stage, &[Action::Pause]);
+        tick(&mut stage, &[Action::Accelerate]);
+        assert!(matches!(
+            stage.machine,
+            Some(GameStageStateMachine::Paused(_))
+        ));
+
+        tick(&mut stage, &[Action::Pause]);
+        tick(&mut stage, &[Action::Accelerate]);
+        let Some(GameStageStateMachine::Playing(state)) = &stage.machine else {
+            panic!("race did not resume");
+        };
+        assert_eq!(state.material.ticks, ticks + 1);
+    }
+
+    #[test]
+    fn driving_straight_crashes_into_a_wall() {
+        let mut stage = loaded_stage();
+        start_race(&mut stage);
+        race_to_the_end(&mut stage, &[Action::Accelerate]);
+
+        let outcome = stage.outcome().expect("race is over");
+        assert!(!outcome.cleared);
+        assert!(outcome.distance < STAGE_GOAL);
+        assert!(matches!(
+            stage.machine,
+            Some(GameStageStateMachine::GameOver(_))
+        ));
+        assert_eq!(
+            stage.replay().map(Replay::len),
+            Some(race_ticks(&stage) as usize)
+        );
+    }
+
+    #[test]
+    fn game_over_goes_back_to_the_title_on_a_new_confirm_press() {
+        let mut stage = loaded_stage();
+        start_race(&mut stage);
+        // Confirm shares its key with Brake, so it may be held when the race ends
+        race_to_the_end(&mut stage, &[Action::Accelerate, Action::Confirm]);
+
+        tick(&mut stage, &[Action::Confirm]);
+        assert!(stage.outcome().is_some());
+        tick(&mut stage, &[]);
+        tick(&mut stage, &[Action::Confirm]);
+        assert!(matches!(
+            stage.machine,
+            Some(GameStageStateMachine::Ready(_))
+        ));
+        assert!(matches!(stage.scenes.last(), Some(Scene::Title(_))));
+    }
+
+    fn race_ticks(stage: &GameStage) -> u32 {
+        match &stage.machine {
+            Some(GameStageStateMachine::GameOver(state)) => state.material.ticks,
+            Some(GameStageStateMachine::GameClear(state)) => state.material.ticks,
+            _ => 0,
+        }
+    }
+}
@@ -1,24 +1,11 @@
 pub mod cart {
     //! Cart module summarizes cart related functions.
 
-    use crate::engine::{Line, Point, Renderer, Velocity};
-    use crate::game::CART_START_Y;
+    use crate::engine::{Line, Point, Renderer, Sprite, Theme, Velocity};
+    use crate::game::Sprites;
+    use crate::geometry::{OrientedBox, Rect};
 
-    /// Constant values in Cart module
-
-    /// Font color used for rendering the cart
-    const FONT_COLOR: &str = "green";
-
-    /// Normal cart appearance - three lines representing the cart visually
-    const CART: [&str; 3] = ["o❚o", " ◆ ", "O❚O"];
-
-    /// Knocked cart appearance - slightly damaged/distorted visual representation
-    const CART_KNOCKED: [&str; 3] = ["O❚ O", " ◆ ", "o ❚o"];
-
-    /// Vertical spacing between cart display lines in pixels
-    const CART_DISTANCE: f32 = 18.0;
-
-    /// Cart width used for collision detection and positioning
+    /// Half of the cart's width, used for collision detection and positioning
     pub const CART_WIDTH: f32 = 20.0;
 
     /// Cart height used for collision detection and positioning
@@ -103,86 +90,65 @@ pub mod cart {
         /// Transitions the cart from running state to knocked state,
         /// changing its visual appearance and behavior.
         pub fn knocked(&mut self) {
-            let _figure: [String; 3] = std::array::from_fn(|i| CART_KNOCKED[i].to_string());
             self.state_machine = self.state_machine.clone().transition(Event::Knocked);
         }
 
         ///
-        /// Checks if the cart intersects with a wall line
+        /// Gets the box the cart collides with
+        ///
+        /// The box hangs below the cart's position, which is the front of the cart.
+        ///
+        /// # Arguments
+        /// * `_position` - Position of the cart
+        ///
+        /// # Returns
+        /// A closed box `CART_WIDTH` to each side of the cart and `CART_HEIGHT` long
+        pub fn hit_box(_position: Point) -> OrientedBox {
+            OrientedBox::new(
+                Point::new(_position.x, _position.y - CART_HEIGHT / 2.0),
+                Velocity { x: 0.0, y: 1.0 },
+                CART_WIDTH,
+                CART_HEIGHT / 2.0,
+            )
+        }
+        ///
+        /// Gets the area the cart can touch during the tick's move
+        ///
+        /// # Arguments
+        /// * `_from` - Position of the cart at the start of the tick, before `run`
+        ///
+        /// # Returns
+        /// The rectangle around the hit box at `_from` and after the next `update`
+        pub fn reach(&self, _from: Point) -> Rect {
+            Cart::hit_box(_from).swept_bounds(self.movement_from(_from))
+        }
         ///
-        /// Performs collision detection by checking if any of the cart's boundary lines
-        /// intersect with the given wall line using line-to-line intersection algorithm.
+        /// Checks if the cart hits a wall line during the tick's move
+        ///
+        /// `run` already moves the cart once, so the move of a tick starts where
+        /// the cart stood before `run` and ends after the following `update`.
+        /// Sweeps the hit box along all of it, so that the cart cannot pass
+        /// through a wall between two ticks however fast it goes. Touching the
+        /// wall, at an end or along a side, counts as a hit.
         ///
         /// # Arguments
+        /// * `_from` - Position of the cart at the start of the tick, before `run`
         /// * `_wall_line` - The wall line to check intersection against
         ///
         /// # Returns
-        /// * `true` - The cart is intersecting/crossing the wall line
-        /// * `false` - The cart is not intersecting the wall line
-        pub fn intersect(&self, _wall_line: Line) -> bool {
-            let mut _cart_lines = vec![];
-
-            // upper side
-            _cart_lines.push(Line::new(
-                Point::new(
-                    self.state_machine.context().position.x - CART_WIDTH,
-                    self.state_machine.context().position.y,
-                ),
-                Point::new(
-                    self.state_machine.context().position.x + CART_WIDTH,
-                    self.state_machine.context().position.y,
-                ),
-            ));
-            // right line
-            _cart_lines.push(Line::new(
-                Point::new(
-                    self.state_machine.context().position.x + CART_WIDTH,
-                    self.state_machine.context().position.y,
-                ),
-                Point::new(
-                    self.state_machine.context().position.x + CART_WIDTH,
-                    self.state_machine.context().position.y + CART_HEIGHT,
-                ),
-            ));
-            // left line
-            _cart_lines.push(Line::new(
-                Point::new(
-                    self.state_machine.context().position.x - CART_WIDTH,
-                    self.state_machine.context().position.y - CART_HEIGHT,
-                ),
-                Point::new(
-                    self.state_machine.context().position.x - CART_WIDTH,
-                    self.state_machine.context().position.y,
-                ),
-            ));
-
-            let mut _ans: bool = false;
-
-            for i in 0.._cart_lines.len() {
-                if ((_cart_lines[i].p.x - _cart_lines[i].q.x)
-                    * (_wall_line.p.y - _cart_lines[i].p.y)
-                    + (_cart_lines[i].p.y - _cart_lines[i].q.y)
-                        * (_cart_lines[i].p.x - _wall_line.p.x))
-                    * ((_cart_lines[i].p.x - _cart_lines[i].q.x)
-                        * (_wall_line.q.y - _cart_lines[i].p.y)
-                        + (_cart_lines[i].p.y - _cart_lines[i].q.y)
-                            * (_cart_lines[i].p.x - _wall_line.q.x))
-                    < 0.0
-                {
-                    if ((_wall_line.p.x - _wall_line.q.x) * (_cart_lines[i].p.y - _wall_line.p.y)
-                        + (_wall_line.p.y - _wall_line.q.y) * (_wall_line.p.x - _cart_lines[i].p.x))
-                        * ((_wall_line.p.x - _wall_line.q.x)
-                            * (_cart_lines[i].q.y - _wall_line.p.y)
-                            + (_wall_line.p.y - _wall_line.q.y)
-                                * (_wall_line.p.x - _cart_lines[i].q.x))
-                        < 0.0
-                    {
-                        _ans = true;
-                        break;
-                    }
-                }
+        /// * `true` - The cart touches the wall line at some point of the move
+        /// * `false` - The cart stays clear of the wall line
+        pub fn intersect(&self, _from: Point, _wall_line: Line) -> bool {
+            Cart::hit_box(_from).sweep_intersects(&_wall_line, self.movement_from(_from))
+        }
+        /// Distance from `_from` to where the next `update` leaves the cart
+        fn movement_from(&self, _from: Point) -> Velocity {
+            let _position = self.get_position();
+            let _velocity = self.get_velocity();
+            Velocity {
+                x: _position.x + _velocity.x - _from.x,
+                y: _position.y + _velocity.y - _from.y,
             }
-            return _ans;
         }
         ///
         /// Gets the current position of the cart
@@ -206,29 +172,39 @@ pub mod cart {
         /// Renders the cart on the screen
         ///
         /// Draws the cart using different visual representations based on its current state.
-        /// Normal carts use the `CART` appearance, while knocked carts use `CART_KNOCKED` appearance.
-        /// Each cart is rendered as multiple text lines with proper vertical spacing.
+        /// Normal carts use the `cart` sprite, while knocked carts use the `cart_knocked` sprite.
+        /// Each appearance is a sprite drawn with its top row at the cart's position.
         ///
         /// # Arguments
         /// * `renderer` - The renderer object used for drawing operations
-        pub fn draw(&self, renderer: &Renderer) {
-            let mut _distance: f32 = 0.0;
-            let mut _figure = self.get_state_machine().context().figure.clone();
+        /// * `theme` - Colors to draw with
+        /// * `sprites` - Figures to draw with
+        pub fn draw(&self, renderer: &dyn Renderer, theme: &Theme, sprites: &Sprites) {
+            let _context = self.state_machine.context();
+            renderer.sprite(&_context.position, self.figure(sprites), theme.cart);
+        }
 
-            let _f: [&str; 3] = std::array::from_fn(|i| _figure[i].as_str());
+        ///
+        /// Renders the cart as a translucent ghost
+        ///
+        /// # Arguments
+        /// * `renderer` - The renderer object used for drawing operations
+        /// * `theme` - Colors to draw with
+        /// * `sprites` - Figures to draw with
+        pub fn draw_ghost(&self, renderer: &dyn Renderer, theme: &Theme, sprites: &Sprites) {
+            let _context = self.state_machine.context();
+            renderer.sprite(
+                &_context.position,
+                &self.figure(sprites).tinted(),
+                theme.ghost,
+            );
+        }
 
-            for i in 0..CART_KNOCKED.len() {
-                renderer.text(
-                    &Point {
-                        x: self.state_machine.context().position.x,
-                        y: CART_START_Y - _distance,
-                    },
-                    _f[i],
-                    FONT_COLOR,
-                    "24px sans-serif",
-                    "center",
-                );
-                _distance += CART_DISTANCE;
+        /// Sprite of the cart's current state
+        fn figure<'a>(&self, sprites: &'a Sprites) -> &'a Sprite {
+            match self.state_machine {
+                CartStateMachine::Knocked(_) => &sprites.cart_knocked,
+                _ => &sprites.cart,
             }
         }
     }
@@ -417,7 +393,7 @@ pub mod cart {
     pub struct Running;
     impl CartState<Running> {
         /// Updates the running cart state by applying velocity to position.
-        /// Moves the cart horizontally based on its current velocity.
+        /// Moves the cart along the track based on its current velocity.
         ///
         /// # Arguments
         /// * `self` - The running cart state (consumed)
@@ -426,6 +402,7 @@ pub mod cart {
         /// Updated CartState<Running> with new position
         pub fn update(mut self) -> CartState<Running> {
             self.context.position.x += self.context.velocity.x;
+            self.context.position.y += self.context.velocity.y;
             self.update_context();
             self
         }
@@ -471,17 +448,11 @@ pub mod cart {
     pub struct CartContext {
         position: Point,
         velocity: Velocity,
-        figure: [String; 3],
     }
 
     impl CartContext {
         fn new(position: Point, velocity: Velocity) -> CartContext {
-            let _figure: [String; 3] = std::array::from_fn(|i| CART[i].to_string());
-            return CartContext {
-                position: position,
-                velocity: velocity,
-                figure: _figure,
-            };
+            CartContext { position, velocity }
         }
         fn update(self) -> Self {
             self
@@ -490,10 +461,85 @@ pub mod cart {
             self.velocity = velocity;
             self
         }
-        fn knocked(mut self) -> Self {
-            let _figure: [String; 3] = std::array::from_fn(|i| CART_KNOCKED[i].to_string());
-            self.figure = _figure;
+        fn knocked(self) -> Self {
             self
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn running_cart(velocity: Velocity) -> Cart {
+            let mut cart = Cart::new(Point::new(300.0, 100.0), Velocity::default());
+            cart.run(velocity);
+            cart
+        }
+
+        #[test]
+        fn run_and_update_each_move_the_cart_by_its_velocity() {
+            let mut cart = running_cart(Velocity { x: 1.0, y: 3.0 });
+            let position = cart.get_position();
+            assert_eq!((position.x, position.y), (301.0, 103.0));
+
+            cart.update();
+            let position = cart.get_position();
+            assert_eq!((position.x, position.y), (302.0, 106.0));
+        }
+
+        #[test]
+        fn idle_cart_stays_put() {
+            let mut cart = Cart::new(Point::new(300.0, 100.0), Velocity { x: 0.0, y: 3.0 });
+            cart.update();
+            let position = cart.get_position();
+            assert_eq!((position.x, position.y), (300.0, 100.0));
+        }
+
+        #[test]
+        fn knocked_cart_stays_put() {
+            let mut cart = running_cart(Velocity { x: 0.0, y: 3.0 });
+            cart.knocked();
+            cart.run(Velocity { x: 0.0, y: 5.0 });
+            cart.update();
+            let position = cart.get_position();
+            assert_eq!((position.x, position.y), (300.0, 103.0));
+            assert!(matches!(cart.state_machine, CartStateMachine::Knocked(_)));
+        }
+
+        #[test]
+        fn intersect_finds_a_wall_anywhere_in_the_move_of_a_tick() {
+            let start = Point::new(300.0, 100.0);
+            let mut cart = Cart::new(start, Velocity::default());
+            // Faster than the cart is long, so each move leaves a gap behind it
+            cart.run(Velocity {
+                x: 0.0,
+                y: CART_HEIGHT + 10.0,
+            });
+            // Crossed by `run`, before the cart's back reached its new place
+            let crossed = Line::new(Point::new(250.0, 105.0), Point::new(350.0, 105.0));
+            // Reached by the following `update`
+            let ahead = Line::new(Point::new(250.0, 220.0), Point::new(350.0, 220.0));
+            let beyond = Line::new(Point::new(250.0, 221.0), Point::new(350.0, 221.0));
+            assert!(cart.intersect(start, crossed));
+            assert!(cart.intersect(start, ahead));
+            assert!(!cart.intersect(start, beyond));
+        }
+
+        #[test]
+        fn intersect_misses_a_wall_beside_the_path() {
+            let start = Point::new(300.0, 100.0);
+            let mut cart = Cart::new(start, Velocity::default());
+            cart.run(Velocity { x: 0.0, y: 5.0 });
+            let beside = Line::new(
+                Point::new(300.0 + CART_WIDTH + 1.0, 0.0),
+                Point::new(300.0 + CART_WIDTH + 1.0, 200.0),
+            );
+            let touching = Line::new(
+                Point::new(300.0 + CART_WIDTH, 0.0),
+                Point::new(300.0 + CART_WIDTH, 200.0),
+            );
+            assert!(!cart.intersect(start, beside));
+            assert!(cart.intersect(start, touching));
+        }
+    }
 }
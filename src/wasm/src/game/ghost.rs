@@ -0,0 +1,51 @@
+pub mod ghost {
+    //! Ghost cart that replays the best run alongside the player.
+    use crate::engine::{Point, Renderer, Theme, Velocity};
+    use crate::game::{CART_START_X, CART_START_Y, Cart, Replay, Sprites, steer};
+
+    ///
+    /// A cart driven by the replay of the best run.
+    ///
+    /// The ghost follows the same movement rules as the player's cart but is
+    /// never checked against walls.
+    pub struct Ghost {
+        cart: Cart,
+        replay: Replay,
+        tick: usize,
+    }
+    impl Ghost {
+        /// Creates a ghost at the start line that will follow `replay`
+        pub fn new(replay: Replay) -> Self {
+            Ghost {
+                cart: Cart::new(
+                    Point {
+                        x: CART_START_X,
+                        y: CART_START_Y,
+                    },
+                    Velocity { x: 0.0, y: 0.0 },
+                ),
+                replay,
+                tick: 0,
+            }
+        }
+
+        /// Advances the ghost by one tick of its replay
+        pub fn update(&mut self) {
+            let _velocity = steer(self.cart.get_velocity(), &self.replay.actions(self.tick));
+            self.tick += 1;
+            self.cart.run(_velocity);
+            self.cart.update();
+        }
+
+        ///
+        /// Renders the ghost at its place on the track
+        ///
+        /// # Arguments
+        /// * `renderer` - The renderer object used for drawing operations, in world positions
+        /// * `theme` - Colors to draw with
+        /// * `sprites` - Figures to draw with
+        pub fn draw(&self, renderer: &dyn Renderer, theme: &Theme, sprites: &Sprites) {
+            self.cart.draw_ghost(renderer, theme, sprites);
+        }
+    }
+}
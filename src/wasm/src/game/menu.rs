@@ -0,0 +1,72 @@
+pub mod menu {
+    //! Vertical list of items picked with the menu actions.
+    use crate::engine::{Action, ActionState, Point, Renderer, Theme};
+    use crate::game::TITLE_MESSAGE_X;
+
+    /// Distance between two items
+    const MENU_DISTANCE: f32 = 60.0;
+
+    ///
+    /// Selection in a menu of `len` items.
+    ///
+    /// MenuUp and MenuDown move the selection, wrapping around at both ends,
+    /// and Confirm picks the selected item. The labels are given when drawing
+    /// so that they can show the current value of a setting.
+    pub struct Menu {
+        len: usize,
+        selected: usize,
+    }
+    impl Menu {
+        /// Creates a menu of `len` items with the first one selected
+        pub fn new(len: usize) -> Self {
+            Menu { len, selected: 0 }
+        }
+
+        /// Index of the selected item
+        pub fn selected(&self) -> usize {
+            self.selected
+        }
+
+        ///
+        /// Moves the selection and checks whether an item was picked
+        ///
+        /// # Arguments
+        /// * `actions` - Actions of the current tick
+        ///
+        /// # Returns
+        /// The index of the item picked with Confirm on this tick, if any
+        pub fn update(&mut self, actions: &ActionState) -> Option<usize> {
+            if actions.is_pressed(Action::MenuUp) {
+                self.selected = (self.selected + self.len - 1) % self.len;
+            }
+            if actions.is_pressed(Action::MenuDown) {
+                self.selected = (self.selected + 1) % self.len;
+            }
+            actions.is_pressed(Action::Confirm).then_some(self.selected)
+        }
+
+        ///
+        /// Renders the items top to bottom, marking the selected one
+        ///
+        /// # Arguments
+        /// * `renderer` - The renderer object used for drawing operations
+        /// * `items` - Labels of the items
+        /// * `theme` - Colors to draw with
+        /// * `top` - Height of the first item
+        pub fn draw(&self, renderer: &dyn Renderer, items: &[&str], theme: &Theme, top: f32) {
+            for (index, item) in items.iter().enumerate() {
+                let _marker = if index == self.selected { "> " } else { "  " };
+                renderer.text(
+                    &Point {
+                        x: TITLE_MESSAGE_X,
+                        y: top - MENU_DISTANCE * index as f32,
+                    },
+                    &format!("{}{}", _marker, item),
+                    theme.hud,
+                    "32px myfont",
+                    "center",
+                );
+            }
+        }
+    }
+}
@@ -2,43 +2,330 @@
 //! Provides functionality for playing sound effects and managing audio resources.
 
 pub mod music {
+    use crate::engine::{EngineSound, Playback};
     use crate::game::{Audio, Sound};
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    /// Seconds the background music takes to fade out when it stops
+    const BACKGROUND_FADE_SECONDS: f64 = 1.5;
+    /// Seconds a sustained effect takes to fade out when it is released, short
+    /// enough to sound like a stop but without a click
+    const RELEASE_SECONDS: f64 = 0.05;
+    /// Seconds the engine takes to fall silent when the race ends
+    const ENGINE_FADE_SECONDS: f64 = 0.3;
+    /// Sound effects that may play at once. The oldest is stopped to make room.
+    const MAX_VOICES: usize = 8;
+
+    ///
+    /// A sound effect and the limits it plays within.
+    #[derive(Clone, Copy)]
+    pub struct Effect {
+        /// Tells the effect apart from others playing the same sound
+        pub id: usize,
+        pub sound: Sound,
+        /// Ticks after a start before the effect can start again
+        pub cooldown: u32,
+        /// Voices of this effect that may play at once. The oldest is stopped to make room.
+        pub max_voices: usize,
+        /// Playback rate, 1 being as recorded
+        pub rate: f32,
+    }
+
+    /// A sound effect the scheduler started and that may still be playing
+    struct Voice {
+        /// `Effect::id` of the effect playing
+        effect: usize,
+        playback: Box<dyn Playback>,
+        /// Looped until the effect is released
+        sustained: bool,
+    }
+
+    ///
+    /// Starts sound effects within their limits.
+    ///
+    /// The clock advances with `update`, once per game tick, so cooldowns are
+    /// counted in ticks. Voices that ended are forgotten on each update.
+    /// Every effect comes from the cart, so they share one stereo position.
+    pub struct Scheduler {
+        audio: Rc<dyn Audio>,
+        tick: u32,
+        /// Stereo position of the effects, from the left at -1 to the right at 1
+        pan: f32,
+        voices: Vec<Voice>,
+        /// Tick each effect was last started on, by `Effect::id`
+        started: HashMap<usize, u32>,
+    }
+    impl Scheduler {
+        pub fn new(audio: Rc<dyn Audio>) -> Self {
+            Scheduler {
+                audio,
+                tick: 0,
+                pan: 0.0,
+                voices: vec![],
+                started: HashMap::new(),
+            }
+        }
+
+        /// Advances the clock by one tick and forgets the voices that ended
+        pub fn update(&mut self) {
+            self.tick += 1;
+            self.voices.retain(|voice| voice.playback.is_playing());
+        }
+
+        ///
+        /// Moves the playing effects and the ones started later
+        ///
+        /// # Arguments
+        /// * `pan` - Stereo position, from the left at -1 to the right at 1
+        pub fn set_pan(&mut self, pan: f32) {
+            if pan == self.pan {
+                return;
+            }
+            self.pan = pan;
+            for voice in &self.voices {
+                if let Err(err) = voice.playback.set_pan(pan) {
+                    log!("Error panning sound effect {:#?}", err);
+                }
+            }
+        }
+
+        ///
+        /// Plays an effect once, unless it is cooling down
+        ///
+        /// # Arguments
+        /// * `effect` - Effect to play
+        pub fn play(&mut self, effect: &Effect) {
+            self.start(effect, false);
+        }
+
+        ///
+        /// Loops an effect while `held` and stops it once released
+        ///
+        /// Call it on every tick with the state of whatever holds the effect.
+        /// Holding it again within the cooldown waits for the cooldown to end.
+        ///
+        /// # Arguments
+        /// * `effect` - Effect to hold
+        /// * `held` - Whether the effect is held on this tick
+        pub fn sustain(&mut self, effect: &Effect, held: bool) {
+            let sustained = self
+                .voices
+                .iter()
+                .position(|voice| voice.effect == effect.id && voice.sustained);
+            match (held, sustained) {
+                (true, None) => self.start(effect, true),
+                (false, Some(index)) => release(self.voices.remove(index)),
+                _ => {}
+            }
+        }
+
+        /// Stops every effect, sustained or not
+        pub fn stop_all(&mut self) {
+            self.voices.drain(..).for_each(release);
+        }
+
+        /// Stops every effect at once, without the release fade, which would
+        /// freeze halfway if the audio were suspended right after
+        pub fn cut_all(&mut self) {
+            for voice in self.voices.drain(..) {
+                if let Err(err) = voice.playback.stop() {
+                    log!("Error stopping sound effect {:#?}", err);
+                }
+            }
+        }
+
+        fn start(&mut self, effect: &Effect, sustained: bool) {
+            if let Some(started) = self.started.get(&effect.id)
+                && self.tick < started + effect.cooldown
+            {
+                return;
+            }
+            let playing = self
+                .voices
+                .iter()
+                .filter(|voice| voice.effect == effect.id)
+                .count();
+            if playing >= effect.max_voices.max(1) {
+                if let Some(oldest) = self
+                    .voices
+                    .iter()
+                    .position(|voice| voice.effect == effect.id)
+                {
+                    release(self.voices.remove(oldest));
+                }
+            } else if self.voices.len() >= MAX_VOICES {
+                release(self.voices.remove(0));
+            }
+
+            let playback = if sustained {
+                self.audio.play_looping_sound(&effect.sound)
+            } else {
+                self.audio.play_sound(&effect.sound)
+            };
+            match playback.and_then(|playback| {
+                playback.set_playback_rate(effect.rate)?;
+                playback.set_pan(self.pan)?;
+                Ok(playback)
+            }) {
+                Ok(playback) => {
+                    self.voices.push(Voice {
+                        effect: effect.id,
+                        playback,
+                        sustained,
+                    });
+                    self.started.insert(effect.id, self.tick);
+                }
+                Err(err) => {
+                    log!("Error playing sound effect {:#?}", err);
+                }
+            }
+        }
+    }
+
+    /// Fades a voice out quickly. Logs an error message if it cannot be stopped.
+    fn release(voice: Voice) {
+        if let Err(err) = voice.playback.fade_out(RELEASE_SECONDS) {
+            log!("Error stopping sound effect {:#?}", err);
+        }
+    }
 
     /// Music struct manages audio playback for game sounds.
-    /// Contains audio interface and sound data for sound effect playback.
-    #[derive(Clone)]
+    /// Contains audio interface, the sound effects with their scheduler, and
+    /// the engine drone and the voice of the background music while they play.
     pub struct Music {
-        pub audio: Audio,
-        pub sound: Sound,
+        pub audio: Rc<dyn Audio>,
+        /// Looped while the cart brakes
+        brake: Effect,
+        /// Played once when the cart hits a wall
+        crash: Effect,
+        effects: Scheduler,
+        engine: Option<Box<dyn EngineSound>>,
+        background_music: Sound,
+        background: Option<Box<dyn Playback>>,
     }
     impl Music {
         /// Creates a new Music instance with the provided audio interface and sound data.
         ///
         /// # Arguments
         /// * `audio` - Audio interface for sound playback
-        /// * `sound` - Sound data to be played
+        /// * `sound` - Brake sound, also played slowed down as the crash sound
+        /// * `background_music` - Song looped while the title menu is shown and during races
         ///
         /// # Returns
-        /// A new Music instance
-        pub fn new(audio: Audio, sound: Sound) -> Self {
+        /// A new Music instance, with the engine and the background music stopped
+        pub fn new(audio: Rc<dyn Audio>, sound: Sound, background_music: Sound) -> Self {
             Music {
-                audio: audio,
-                sound: sound,
+                brake: Effect {
+                    id: 0,
+                    sound,
+                    // Keeps a trigger resting at the threshold from stuttering
+                    cooldown: 6,
+                    max_voices: 1,
+                    rate: 1.0,
+                },
+                crash: Effect {
+                    id: 1,
+                    sound,
+                    cooldown: 30,
+                    max_voices: 1,
+                    rate: 0.5,
+                },
+                effects: Scheduler::new(audio.clone()),
+                engine: None,
+                audio,
+                background_music,
+                background: None,
             }
         }
-        /// Plays the brake sound effect using the audio interface.
-        /// Logs an error message if sound playback fails.
+        ///
+        /// Advances the sound effects and the engine by one tick of the race.
+        /// Starts the engine on the first tick. Logs an error message if it cannot be played.
         ///
         /// # Arguments
-        /// * `self` - Music instance (consumed and returned)
+        /// * `braking` - Whether the cart brakes on this tick, which holds the brake sound
+        /// * `speed` - Forward speed of the cart, from standing at 0 to the speed limit at 1
+        /// * `pan` - Stereo position of the cart, from the left at -1 to the right at 1
+        pub fn update(&mut self, braking: bool, speed: f32, pan: f32) {
+            self.effects.update();
+            self.effects.set_pan(pan);
+            let brake = self.brake;
+            self.effects.sustain(&brake, braking);
+
+            if self.engine.is_none() {
+                match self.audio.play_engine_sound() {
+                    Ok(engine) => self.engine = Some(engine),
+                    Err(err) => {
+                        log!("Error playing engine sound {:#?}", err);
+                    }
+                }
+            }
+            if let Some(engine) = &self.engine
+                && let Err(err) = engine.set_speed(speed)
+            {
+                log!("Error changing engine sound {:#?}", err);
+            }
+        }
+        /// Lets the engine fall silent until the next race starts it again.
+        /// Logs an error message if it cannot be stopped.
+        pub fn stop_engine(&mut self) {
+            if let Some(engine) = self.engine.take()
+                && let Err(err) = engine.fade_out(ENGINE_FADE_SECONDS)
+            {
+                log!("Error stopping engine sound {:#?}", err);
+            }
+        }
         ///
-        /// # Returns
-        /// The same Music instance for method chaining
-        pub fn play_brake_sound(self) -> Self {
-            if let Err(err) = self.audio.play_sound(&self.sound) {
-                log!("Error playing jump sound {:#?}", err);
+        /// Stops the brake sound and plays the crash sound
+        ///
+        /// # Arguments
+        /// * `pan` - Stereo position of the crash, from the left at -1 to the right at 1
+        pub fn play_crash_sound(&mut self, pan: f32) {
+            self.effects.stop_all();
+            self.effects.set_pan(pan);
+            let crash = self.crash;
+            self.effects.play(&crash);
+        }
+        /// Stops every sound effect, e.g. when the race ends
+        pub fn stop_effects(&mut self) {
+            self.effects.stop_all();
+        }
+        /// Starts looping the background music, unless it is already playing.
+        /// Logs an error message if the music cannot be played.
+        pub fn start_background(&mut self) {
+            if self.background.is_some() {
+                return;
+            }
+            match self.audio.play_looping_sound(&self.background_music) {
+                Ok(playback) => self.background = Some(playback),
+                Err(err) => {
+                    log!("Error playing background music {:#?}", err);
+                }
+            }
+        }
+        /// Fades the background music out until `start_background` starts it again.
+        /// Logs an error message if the music cannot be stopped.
+        pub fn stop_background(&mut self) {
+            if let Some(playback) = self.background.take()
+                && let Err(err) = playback.fade_out(BACKGROUND_FADE_SECONDS)
+            {
+                log!("Error stopping background music {:#?}", err);
+            }
+        }
+        /// Cuts the sound effects, then pauses the background music and every
+        /// other sound until `resume`. Logs an error message if the audio cannot be paused.
+        pub fn pause(&mut self) {
+            self.effects.cut_all();
+            if let Err(err) = self.audio.suspend() {
+                log!("Error pausing audio {:#?}", err);
+            }
+        }
+        /// Resumes the sounds paused by `pause`.
+        /// Logs an error message if the audio cannot be resumed.
+        pub fn resume(&self) {
+            if let Err(err) = self.audio.resume() {
+                log!("Error resuming audio {:#?}", err);
             }
-            self
         }
     }
 }
@@ -1,19 +1,15 @@
 pub mod ornament {
     //! ornament related functions.
-    use crate::engine::{Point, Velocity};
-    use crate::game::{Piece, Renderer, State, StateMachine};
+    use crate::engine::{Point, Theme, Velocity};
+    use crate::game::{Piece, Renderer, Sprites, State, StateMachine};
+    use crate::geometry::Rect;
 
     /* <-- CONSTANT VALUE */
-    const TREE: [&str; 4] = [" $ ", " $$ ", "$$$", " ▯ "];
-    const TREE_DISTANCE: f32 = 12.0;
-    const FONT_COLOR: &str = "green";
     const GOAL_X: f32 = 100.0;
     const GOAL_Y: f32 = 7450.0;
-    const GOAL: [&str; 2] = [
-        "□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■",
-        "□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■□□■",
-    ];
-    const GOAL_DISTANCE: f32 = 25.0;
+    /// Distance a sprite may reach above or below its position
+    const SPRITE_MARGIN: f32 = 100.0;
+    /* CONSTANT VALUE --> */
 
     pub struct Ornament {
         pub state_machine: StateMachine,
@@ -31,21 +27,43 @@ pub mod ornament {
             self.state_machine = _state_machine.update();
         }
 
-        fn draw(&self, renderer: &Renderer) {
+        fn draw(&self, renderer: &dyn Renderer, theme: &Theme, sprites: &Sprites) {
+            let _everywhere = Rect {
+                min: Point::new(f32::NEG_INFINITY, f32::NEG_INFINITY),
+                max: Point::new(f32::INFINITY, f32::INFINITY),
+            };
+            self.draw_visible(renderer, theme, sprites, &_everywhere);
+        }
+    }
+    impl Ornament {
+        ///
+        /// Draws the goal and the trees that can be seen
+        ///
+        /// The course fits the screen across, so sprites are only skipped when
+        /// they are above or below the view.
+        ///
+        /// # Arguments
+        /// * `renderer` - Reference to the renderer for drawing operations
+        /// * `theme` - Colors to draw with
+        /// * `sprites` - Figures to draw with
+        /// * `view` - World area on screen
+        pub fn draw_visible(
+            &self,
+            renderer: &dyn Renderer,
+            theme: &Theme,
+            sprites: &Sprites,
+            view: &Rect,
+        ) {
+            let _visible = |point: &Point| {
+                point.y + SPRITE_MARGIN >= view.min.y && point.y - SPRITE_MARGIN <= view.max.y
+            };
             // GOAL
-            let mut _distance: f32 = 0.0;
-            for i in 0..GOAL.len() {
-                renderer.text(
-                    &Point {
-                        x: GOAL_X + self.state_machine.context().p.x,
-                        y: GOAL_Y + self.state_machine.context().p.y + _distance,
-                    },
-                    GOAL[i],
-                    FONT_COLOR,
-                    "32 myfont",
-                    "center",
-                );
-                _distance += GOAL_DISTANCE;
+            let _goal = Point {
+                x: GOAL_X + self.state_machine.context().p.x,
+                y: GOAL_Y + self.state_machine.context().p.y,
+            };
+            if _visible(&_goal) {
+                renderer.sprite(&_goal, &sprites.goal, theme.goal);
             }
 
             let trees = [
@@ -62,19 +80,12 @@ pub mod ornament {
             ];
 
             for tree in trees.iter() {
-                _distance = 0.0;
-                for i in 0..TREE.len() {
-                    renderer.text(
-                        &Point {
-                            x: tree.0 + self.state_machine.context().p.x,
-                            y: tree.1 + self.state_machine.context().p.y - _distance,
-                        },
-                        TREE[i],
-                        FONT_COLOR,
-                        "32 myfont",
-                        "center",
-                    );
-                    _distance += TREE_DISTANCE;
+                let _tree = Point {
+                    x: tree.0 + self.state_machine.context().p.x,
+                    y: tree.1 + self.state_machine.context().p.y,
+                };
+                if _visible(&_tree) {
+                    renderer.sprite(&_tree, &sprites.tree, theme.scenery);
                 }
             }
         }
@@ -0,0 +1,214 @@
+pub mod replay {
+    //! Recording and playback of the actions held on each tick of a race.
+    use crate::engine::{ACTION_LEVEL_MAX, Action, ActionState};
+    use anyhow::{Result, anyhow};
+
+    /// Actions recorded on every tick, in the order they appear in a frame.
+    const ACTIONS: [Action; 5] = [
+        Action::Accelerate,
+        Action::Straighten,
+        Action::SteerLeft,
+        Action::SteerRight,
+        Action::Brake,
+    ];
+
+    /// First token of an encoded replay, bumped whenever the format changes
+    const HEADER: &str = "cart-replay-2";
+
+    /// Header of replays recorded before analog input, which only hold bit masks
+    const HEADER_V1: &str = "cart-replay-1";
+
+    /// Level of each of the `ACTIONS` on one tick
+    type Frame = [u8; ACTIONS.len()];
+
+    ///
+    /// Actions held on each tick of a race.
+    ///
+    /// The text form is the header followed by run-length encoded frames,
+    /// `<frame>*<ticks>`. A frame where every action is either released or
+    /// fully held is written as a hex bit mask in `ACTIONS` order; other frames
+    /// list every level in hex, separated by commas.
+    /// e.g. `cart-replay-2 0*1 1*240 9*60 ff,0,80,0,0*30`.
+    #[derive(Clone, Default)]
+    pub struct Replay {
+        frames: Vec<Frame>,
+    }
+    impl Replay {
+        /// Appends the actions held in `actions` as the next tick
+        pub fn record(&mut self, actions: &ActionState) {
+            self.frames
+                .push(ACTIONS.map(|action| actions.level(action)));
+        }
+
+        /// Gets the actions held on `tick`. Ticks past the end hold nothing.
+        pub fn actions(&self, tick: usize) -> ActionState {
+            let mut actions = ActionState::new();
+            let frame = self.frames.get(tick).copied().unwrap_or_default();
+            for (action, level) in ACTIONS.iter().zip(frame) {
+                actions.set_level(*action, level);
+            }
+            actions
+        }
+
+        /// Number of recorded ticks
+        pub fn len(&self) -> usize {
+            self.frames.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.frames.is_empty()
+        }
+
+        /// Encodes the replay into its text form
+        pub fn encode(&self) -> String {
+            let mut text = HEADER.to_string();
+            let mut frames = self.frames.iter().peekable();
+            while let Some(&frame) = frames.next() {
+                let mut count = 1;
+                while frames.next_if_eq(&&frame).is_some() {
+                    count += 1;
+                }
+                text.push_str(&format!(" {}*{}", encode_frame(&frame), count));
+            }
+            text
+        }
+
+        /// Decodes a replay from its text form
+        pub fn decode(text: &str) -> Result<Replay> {
+            let mut tokens = text.split_whitespace();
+            match tokens.next() {
+                Some(HEADER) | Some(HEADER_V1) => {}
+                _ => return Err(anyhow!("Replay does not start with {}", HEADER)),
+            }
+            let mut frames = vec![];
+            for token in tokens {
+                let (frame, count) = token
+                    .split_once('*')
+                    .ok_or_else(|| anyhow!("Invalid replay run {:?}", token))?;
+                let frame = decode_frame(frame)
+                    .ok_or_else(|| anyhow!("Invalid action levels in replay run {:?}", token))?;
+                let count: usize = count
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid tick count in replay run {:?}", token))?;
+                frames.extend(std::iter::repeat_n(frame, count));
+            }
+            Ok(Replay { frames })
+        }
+    }
+
+    fn encode_frame(frame: &Frame) -> String {
+        if frame
+            .iter()
+            .all(|level| *level == 0 || *level == ACTION_LEVEL_MAX)
+        {
+            let mask = frame
+                .iter()
+                .enumerate()
+                .filter(|(_, level)| **level != 0)
+                .fold(0, |mask, (bit, _)| mask | 1 << bit);
+            format!("{:x}", mask)
+        } else {
+            frame
+                .iter()
+                .map(|level| format!("{:x}", level))
+                .collect::<Vec<_>>()
+                .join(",")
+        }
+    }
+
+    fn decode_frame(text: &str) -> Option<Frame> {
+        if text.contains(',') {
+            let levels = text
+                .split(',')
+                .map(|level| u8::from_str_radix(level, 16).ok())
+                .collect::<Option<Vec<u8>>>()?;
+            levels.try_into().ok()
+        } else {
+            let mask = u8::from_str_radix(text, 16)
+                .ok()
+                .filter(|mask| *mask >> ACTIONS.len() == 0)?;
+            Some(std::array::from_fn(|bit| {
+                if mask & 1 << bit != 0 {
+                    ACTION_LEVEL_MAX
+                } else {
+                    0
+                }
+            }))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn frame(held: &[(Action, u8)]) -> ActionState {
+            let mut actions = ActionState::new();
+            for (action, level) in held {
+                actions.set_level(*action, *level);
+            }
+            actions
+        }
+
+        #[test]
+        fn encode_runs_full_frames_as_masks_and_partial_ones_as_levels() {
+            let mut replay = Replay::default();
+            replay.record(&frame(&[]));
+            for _ in 0..3 {
+                replay.record(&frame(&[(Action::Accelerate, ACTION_LEVEL_MAX)]));
+            }
+            replay.record(&frame(&[
+                (Action::Accelerate, ACTION_LEVEL_MAX),
+                (Action::SteerLeft, 0x80),
+            ]));
+            assert_eq!(replay.encode(), "cart-replay-2 0*1 1*3 ff,0,80,0,0*1");
+        }
+
+        #[test]
+        fn decode_gives_back_the_encoded_actions() {
+            let mut replay = Replay::default();
+            replay.record(&frame(&[(Action::Brake, ACTION_LEVEL_MAX)]));
+            replay.record(&frame(&[
+                (Action::Accelerate, ACTION_LEVEL_MAX),
+                (Action::SteerRight, 0x2a),
+            ]));
+            replay.record(&frame(&[(Action::Straighten, ACTION_LEVEL_MAX)]));
+
+            let decoded = Replay::decode(&replay.encode()).unwrap();
+            assert_eq!(decoded.len(), replay.len());
+            for tick in 0..replay.len() {
+                for action in ACTIONS {
+                    assert_eq!(
+                        decoded.actions(tick).level(action),
+                        replay.actions(tick).level(action)
+                    );
+                }
+            }
+            assert_eq!(decoded.encode(), replay.encode());
+        }
+
+        #[test]
+        fn decode_reads_bit_mask_replays_of_the_first_format() {
+            let replay = Replay::decode("cart-replay-1 0*1 11*2").unwrap();
+            assert_eq!(replay.len(), 3);
+            assert!(!replay.actions(0).is_active(Action::Accelerate));
+            assert!(replay.actions(2).is_active(Action::Accelerate));
+            assert!(replay.actions(2).is_active(Action::Brake));
+            assert!(!replay.actions(3).is_active(Action::Accelerate));
+        }
+
+        #[test]
+        fn decode_rejects_malformed_text() {
+            for text in [
+                "",
+                "cart-replay-3 1*1",
+                "cart-replay-2 1",
+                "cart-replay-2 1*x",
+                "cart-replay-2 20*1",
+                "cart-replay-2 ff,0*1",
+                "cart-replay-2 ff,0,0,0,100*1",
+            ] {
+                assert!(Replay::decode(text).is_err(), "{:?} decoded", text);
+            }
+        }
+    }
+}
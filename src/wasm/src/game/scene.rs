@@ -0,0 +1,459 @@
+pub mod scene {
+    //! Screens shown on top of the race, such as the title menu and options.
+    use crate::engine::{
+        Action, ActionState, Bus, KeyBindings, MixerSettings, Point, Renderer, Theme, VOLUME_STEP,
+    };
+    use crate::game::{
+        MESSAGE_DISTANCE, MESSAGE_THEME_Y, Menu, TITLE, TITLE_MESSAGE_X, TITLE_MESSAGE_Y, TITLE_X,
+        TITLE_Y,
+    };
+    const TITLE_MENU: [&str; 5] = ["Play", "Time Trial", "Options", "Controls", "Credits"];
+    const TITLE_MENU_Y: f32 = 600.0;
+    const OPTIONS_MENU_Y: f32 = 640.0;
+    /// Rows of the options menu: theme, a volume slider per bus, mute and back
+    const OPTIONS_MENU_LEN: usize = 6;
+    const OPTIONS_HINT: &str = "Left / Right: volume    [ M ] Mute";
+    /// Actions the controls screen lets the player bind, in menu order
+    const REBINDABLE: [Action; 7] = [
+        Action::Accelerate,
+        Action::Brake,
+        Action::SteerLeft,
+        Action::SteerRight,
+        Action::Straighten,
+        Action::Pause,
+        Action::WatchReplay,
+    ];
+    /// Rows of the controls menu: an action each, then reset and back
+    const CONTROLS_MENU_LEN: usize = REBINDABLE.len() + 2;
+    const CONTROLS_MENU_Y: f32 = 700.0;
+    const CONTROLS_HINT: &str = "Pick an action, then press its new key";
+    const MESSAGE_BACK: &str = "Push Space Key to go back.";
+    const MESSAGE_BACK_Y: f32 = 80.0;
+    const CREDITS: [&str; 4] = [
+        "Game by myurioka",
+        "Sound effects from GameSounds.xyz",
+        "Font: FFF Forward",
+        "Made with Rust & WebAssembly",
+    ];
+
+    ///
+    /// A screen on the scene stack.
+    ///
+    /// The top scene takes all input and is drawn instead of the race. The race
+    /// only runs while the stack is empty.
+    pub enum Scene {
+        Title(Menu),
+        Options(Menu),
+        /// Key bindings, with the action whose new key is awaited and the
+        /// action already using the last key refused for it
+        Controls {
+            menu: Menu,
+            waiting: Option<Action>,
+            taken_by: Option<Action>,
+        },
+        Credits,
+    }
+
+    /// What the top scene asks the game stage to do after an update
+    pub enum SceneEndState {
+        Continue,
+        Push(Scene),
+        Pop,
+        /// Close every scene and start a race, against the clock of the best run for a time trial
+        Play {
+            time_trial: bool,
+        },
+        NextTheme,
+        /// Apply new mixer volumes
+        Mixer(MixerSettings),
+        /// Apply and save new key bindings
+        KeyBindings(KeyBindings),
+    }
+
+    impl Scene {
+        /// Title menu shown while no race is running
+        pub fn title() -> Self {
+            Scene::Title(Menu::new(TITLE_MENU.len()))
+        }
+
+        /// Handle input for the scene, given the current mixer volumes and key bindings
+        pub fn update(
+            &mut self,
+            _actions: &ActionState,
+            mixer: &MixerSettings,
+            bindings: &KeyBindings,
+        ) -> SceneEndState {
+            match self {
+                Scene::Title(menu) => {
+                    if _actions.is_pressed(Action::NextTheme) {
+                        return SceneEndState::NextTheme;
+                    }
+                    match menu.update(_actions) {
+                        Some(0) => SceneEndState::Play { time_trial: false },
+                        Some(1) => SceneEndState::Play { time_trial: true },
+                        Some(2) => SceneEndState::Push(Scene::Options(Menu::new(OPTIONS_MENU_LEN))),
+                        Some(3) => SceneEndState::Push(Scene::Controls {
+                            menu: Menu::new(CONTROLS_MENU_LEN),
+                            waiting: None,
+                            taken_by: None,
+                        }),
+                        Some(_) => SceneEndState::Push(Scene::Credits),
+                        None => SceneEndState::Continue,
+                    }
+                }
+                Scene::Options(menu) => {
+                    if _actions.is_pressed(Action::Pause) {
+                        return SceneEndState::Pop;
+                    }
+                    if let Some(bus) = options_bus(menu.selected()) {
+                        let _step = if _actions.is_pressed(Action::SteerRight) {
+                            VOLUME_STEP
+                        } else if _actions.is_pressed(Action::SteerLeft) {
+                            -VOLUME_STEP
+                        } else {
+                            0.0
+                        };
+                        if _step != 0.0 {
+                            let mut mixer = *mixer;
+                            mixer.set_volume(bus, mixer.volume(bus) + _step);
+                            return SceneEndState::Mixer(mixer);
+                        }
+                    }
+                    match menu.update(_actions) {
+                        Some(0) => SceneEndState::NextTheme,
+                        Some(4) => {
+                            let mut mixer = *mixer;
+                            mixer.muted = !mixer.muted;
+                            SceneEndState::Mixer(mixer)
+                        }
+                        Some(5) => SceneEndState::Pop,
+                        Some(_) | None => SceneEndState::Continue,
+                    }
+                }
+                Scene::Controls {
+                    menu,
+                    waiting,
+                    taken_by,
+                } => {
+                    // Any key pressed while waiting is tried as the new key, even one bound to Pause
+                    if let Some(action) = *waiting {
+                        let Some(code) = _actions.key() else {
+                            return SceneEndState::Continue;
+                        };
+                        let mut bindings = bindings.clone();
+                        if let Err(owner) = bindings.rebind(action, code) {
+                            *taken_by = Some(owner);
+                            return SceneEndState::Continue;
+                        }
+                        *waiting = None;
+                        *taken_by = None;
+                        return SceneEndState::KeyBindings(bindings);
+                    }
+                    if _actions.is_pressed(Action::Pause) {
+                        return SceneEndState::Pop;
+                    }
+                    match menu.update(_actions) {
+                        Some(index) if index < REBINDABLE.len() => {
+                            *waiting = Some(REBINDABLE[index]);
+                            SceneEndState::Continue
+                        }
+                        Some(index) if index == REBINDABLE.len() => {
+                            SceneEndState::KeyBindings(KeyBindings::default())
+                        }
+                        Some(_) => SceneEndState::Pop,
+                        None => SceneEndState::Continue,
+                    }
+                }
+                Scene::Credits => {
+                    if _actions.is_pressed(Action::Confirm) || _actions.is_pressed(Action::Pause) {
+                        SceneEndState::Pop
+                    } else {
+                        SceneEndState::Continue
+                    }
+                }
+            }
+        }
+
+        ///
+        /// Renders the scene
+        ///
+        /// # Arguments
+        /// * `renderer` - The renderer object used for drawing operations
+        /// * `theme` - Colors to draw with
+        /// * `mixer` - Mixer volumes shown on the options screen
+        /// * `bindings` - Key bindings shown on the controls screen
+        pub fn draw(
+            &self,
+            renderer: &dyn Renderer,
+            theme: &Theme,
+            mixer: &MixerSettings,
+            bindings: &KeyBindings,
+        ) {
+            match self {
+                Scene::Title(menu) => {
+                    draw_heading(renderer, theme, TITLE, "120px myfont");
+                    menu.draw(renderer, &TITLE_MENU, theme, TITLE_MENU_Y);
+                    renderer.text(
+                        &Point {
+                            x: TITLE_MESSAGE_X,
+                            y: MESSAGE_THEME_Y,
+                        },
+                        &format!("Theme: {}  [ T ]", theme.name),
+                        theme.hud,
+                        "24px selif",
+                        "center",
+                    );
+                }
+                Scene::Options(menu) => {
+                    draw_heading(renderer, theme, "Options", "72px myfont");
+                    let mut _items = vec![format!("Theme: {}", theme.name)];
+                    for bus in [Bus::Master, Bus::Music, Bus::Effects] {
+                        _items.push(volume_slider(bus, mixer.volume(bus)));
+                    }
+                    _items.push(format!("Sound: {}", if mixer.muted { "Off" } else { "On" }));
+                    _items.push("Back".to_string());
+                    let _items: Vec<&str> = _items.iter().map(String::as_str).collect();
+                    menu.draw(renderer, &_items, theme, OPTIONS_MENU_Y);
+                    renderer.text(
+                        &Point {
+                            x: TITLE_MESSAGE_X,
+                            y: MESSAGE_BACK_Y,
+                        },
+                        OPTIONS_HINT,
+                        theme.hud,
+                        "24px selif",
+                        "center",
+                    );
+                }
+                Scene::Controls {
+                    menu,
+                    waiting,
+                    taken_by,
+                } => {
+                    draw_heading(renderer, theme, "Controls", "72px myfont");
+                    let mut _items: Vec<String> = REBINDABLE
+                        .iter()
+                        .map(|&action| {
+                            let _keys = if *waiting == Some(action) {
+                                "...".to_string()
+                            } else {
+                                key_labels(bindings.codes(action))
+                            };
+                            format!("{}  [ {} ]", action_name(action), _keys)
+                        })
+                        .collect();
+                    _items.push("Reset Keys".to_string());
+                    _items.push("Back".to_string());
+                    let _items: Vec<&str> = _items.iter().map(String::as_str).collect();
+                    menu.draw(renderer, &_items, theme, CONTROLS_MENU_Y);
+                    let _hint = match (waiting, taken_by) {
+                        (Some(action), Some(owner)) => format!(
+                            "{} uses that key, press another for {}",
+                            action_name(*owner),
+                            action_name(*action)
+                        ),
+                        (Some(action), None) => format!("Press a key for {}", action_name(*action)),
+                        (None, _) => CONTROLS_HINT.to_string(),
+                    };
+                    renderer.text(
+                        &Point {
+                            x: TITLE_MESSAGE_X,
+                            y: MESSAGE_BACK_Y,
+                        },
+                        &_hint,
+                        theme.hud,
+                        "24px selif",
+                        "center",
+                    );
+                }
+                Scene::Credits => {
+                    draw_heading(renderer, theme, "Credits", "72px myfont");
+                    for (index, line) in CREDITS.iter().enumerate() {
+                        renderer.text(
+                            &Point {
+                                x: TITLE_MESSAGE_X,
+                                y: TITLE_MESSAGE_Y - MESSAGE_DISTANCE * index as f32,
+                            },
+                            line,
+                            theme.hud,
+                            "32px selif",
+                            "center",
+                        );
+                    }
+                    draw_back_message(renderer, theme);
+                }
+            }
+        }
+    }
+
+    /// Bus whose volume the options row at `index` sets
+    fn options_bus(index: usize) -> Option<Bus> {
+        match index {
+            1 => Some(Bus::Master),
+            2 => Some(Bus::Music),
+            3 => Some(Bus::Effects),
+            _ => None,
+        }
+    }
+
+    /// Name of an action as the controls screen shows it
+    fn action_name(action: Action) -> &'static str {
+        match action {
+            Action::Accelerate => "Accelerate",
+            Action::Brake => "Brake",
+            Action::SteerLeft => "Steer Left",
+            Action::SteerRight => "Steer Right",
+            Action::Straighten => "Straighten",
+            Action::Confirm => "Confirm",
+            Action::Pause => "Pause",
+            Action::WatchReplay => "Replay",
+            Action::MenuUp => "Menu Up",
+            Action::MenuDown => "Menu Down",
+            Action::NextTheme => "Next Theme",
+            Action::Mute => "Mute",
+        }
+    }
+
+    /// Labels of the keys bound to an action, e.g. `UP / W` for `ArrowUp,KeyW`
+    fn key_labels(codes: &[String]) -> String {
+        if codes.is_empty() {
+            return "-".to_string();
+        }
+        codes
+            .iter()
+            .map(|code| {
+                ["Key", "Digit", "Arrow"]
+                    .iter()
+                    .find_map(|prefix| code.strip_prefix(prefix))
+                    .unwrap_or(code)
+                    .to_uppercase()
+            })
+            .collect::<Vec<_>>()
+            .join(" / ")
+    }
+
+    /// Label of a volume slider, e.g. `Music [######----]`
+    fn volume_slider(bus: Bus, volume: f32) -> String {
+        let steps = (1.0 / VOLUME_STEP).round() as usize;
+        let filled = ((volume / VOLUME_STEP).round() as usize).min(steps);
+        let name = match bus {
+            Bus::Master => "Master",
+            Bus::Music => "Music",
+            Bus::Effects => "Effects",
+        };
+        format!(
+            "{} [{}{}]",
+            name,
+            "#".repeat(filled),
+            "-".repeat(steps - filled)
+        )
+    }
+
+    fn draw_heading(renderer: &dyn Renderer, theme: &Theme, heading: &str, font: &str) {
+        renderer.text(
+            &Point {
+                x: TITLE_X,
+                y: TITLE_Y,
+            },
+            heading,
+            theme.hud,
+            font,
+            "center",
+        );
+    }
+
+    fn draw_back_message(renderer: &dyn Renderer, theme: &Theme) {
+        renderer.text(
+            &Point {
+                x: TITLE_MESSAGE_X,
+                y: MESSAGE_BACK_Y,
+            },
+            MESSAGE_BACK,
+            theme.hud,
+            "24px selif",
+            "center",
+        );
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn controls() -> Scene {
+            Scene::Controls {
+                menu: Menu::new(CONTROLS_MENU_LEN),
+                waiting: None,
+                taken_by: None,
+            }
+        }
+
+        /// Actions of a tick where `pressed` became active and `key` went down
+        fn press(pressed: &[Action], key: Option<&str>) -> ActionState {
+            let mut actions = ActionState::new();
+            pressed
+                .iter()
+                .for_each(|action| actions.set_active(*action));
+            if let Some(key) = key {
+                actions.set_key(key);
+            }
+            actions
+        }
+
+        #[test]
+        fn controls_bind_the_next_key_to_the_picked_action() {
+            let mut scene = controls();
+            let (mixer, bindings) = (MixerSettings::default(), KeyBindings::default());
+            let mut update = |actions: ActionState| scene.update(&actions, &mixer, &bindings);
+
+            assert!(matches!(
+                update(press(&[Action::MenuDown], Some("ArrowDown"))),
+                SceneEndState::Continue
+            ));
+            assert!(matches!(
+                update(press(&[Action::Confirm], Some("Space"))),
+                SceneEndState::Continue
+            ));
+            // Nothing happens until a key goes down
+            assert!(matches!(update(press(&[], None)), SceneEndState::Continue));
+            // Pause already uses KeyP, so Brake keeps waiting
+            assert!(matches!(
+                update(press(&[Action::Pause], Some("KeyP"))),
+                SceneEndState::Continue
+            ));
+            match update(press(&[], Some("KeyB"))) {
+                SceneEndState::KeyBindings(bindings) => {
+                    assert_eq!(bindings.codes(Action::Brake), ["KeyB"]);
+                    assert_eq!(bindings.codes(Action::Accelerate), ["ArrowUp"]);
+                }
+                _ => panic!("no new key bindings"),
+            }
+            assert!(matches!(
+                update(press(&[Action::Pause], None)),
+                SceneEndState::Pop
+            ));
+        }
+
+        #[test]
+        fn controls_reset_keys_to_the_defaults() {
+            let mut scene = controls();
+            let bindings = KeyBindings::decode("accelerate=KeyW").unwrap();
+            let mixer = MixerSettings::default();
+            for _ in 0..REBINDABLE.len() {
+                scene.update(&press(&[Action::MenuDown], None), &mixer, &bindings);
+            }
+            match scene.update(&press(&[Action::Confirm], None), &mixer, &bindings) {
+                SceneEndState::KeyBindings(bindings) => {
+                    assert_eq!(bindings.codes(Action::Accelerate), ["ArrowUp"]);
+                }
+                _ => panic!("no new key bindings"),
+            }
+        }
+
+        #[test]
+        fn key_labels_drop_code_prefixes() {
+            let codes = ["ArrowUp", "KeyW", "Digit1", "Space"].map(String::from);
+            assert_eq!(key_labels(&codes), "UP / W / 1 / SPACE");
+            assert_eq!(key_labels(&[]), "-");
+        }
+    }
+}
@@ -0,0 +1,112 @@
+pub mod touch {
+    //! On-screen touch controls drawn over the track.
+    use crate::engine::{Action, ActionState, Point, Renderer, Theme};
+    use crate::game::CANVAS_WIDTH;
+
+    /// Width of the button columns on each side of the track
+    const BUTTON_WIDTH: f32 = 95.0;
+
+    /// Height of each button
+    const BUTTON_HEIGHT: f32 = 200.0;
+
+    /// Buttons as (left, bottom, label, action), in screen coordinates at the
+    /// bottom corners. They keep to the margins outside the walls for most of
+    /// the track, but the left wall bends out to x = 50 around y = 1800 and the
+    /// trees stand at the left edge, so there the left column overlaps the
+    /// course and can cover part of the cart. Buttons are drawn as outlines, so
+    /// what is under them still shows.
+    const BUTTONS: [(f32, f32, &str, Action); 4] = [
+        (0.0, 0.0, "◀", Action::SteerLeft),
+        (0.0, BUTTON_HEIGHT, "BRK", Action::Brake),
+        (CANVAS_WIDTH - BUTTON_WIDTH, 0.0, "▶", Action::SteerRight),
+        (
+            CANVAS_WIDTH - BUTTON_WIDTH,
+            BUTTON_HEIGHT,
+            "GO",
+            Action::Accelerate,
+        ),
+    ];
+
+    ///
+    /// Touch buttons for steering, accelerating and braking.
+    ///
+    /// Holding a steering button steers, and lifting the finger off it
+    /// straightens the cart. A touch anywhere outside the buttons confirms,
+    /// which starts and restarts the race.
+    #[derive(Default)]
+    pub struct TouchControls {
+        steering: bool,
+        confirming: bool,
+    }
+    impl TouchControls {
+        ///
+        /// Holds the actions under the current touches
+        ///
+        /// # Arguments
+        /// * `touches` - Positions of the fingers on the canvas in game coordinates
+        /// * `actions` - Action state to add the held actions to
+        pub fn apply(&mut self, touches: &[Point], actions: &mut ActionState) {
+            let mut steering = false;
+            let mut confirming = false;
+            for touch in touches {
+                match button_at(touch) {
+                    Some(action @ (Action::SteerLeft | Action::SteerRight)) => {
+                        steering = true;
+                        actions.set_active(action);
+                    }
+                    Some(action) => actions.set_active(action),
+                    None => confirming = true,
+                }
+            }
+            if self.steering && !steering {
+                actions.set_active(Action::Straighten);
+            }
+            // Confirm only on the first tick of a touch so a held finger does not restart again.
+            // The game loop applies the controls once per tick, so the tick always sees it.
+            if confirming && !self.confirming {
+                actions.set_active(Action::Confirm);
+            }
+            self.steering = steering;
+            self.confirming = confirming;
+        }
+
+        ///
+        /// Renders the button outlines and labels
+        ///
+        /// # Arguments
+        /// * `renderer` - The renderer object used for drawing operations
+        /// * `theme` - Colors to draw with
+        pub fn draw(&self, renderer: &dyn Renderer, theme: &Theme) {
+            for (left, bottom, label, _) in BUTTONS {
+                let right = left + BUTTON_WIDTH;
+                let top = bottom + BUTTON_HEIGHT;
+                let corners = [
+                    Point::new(left, bottom),
+                    Point::new(right, bottom),
+                    Point::new(right, top),
+                    Point::new(left, top),
+                ];
+                for i in 0..corners.len() {
+                    renderer.line(&corners[i], &corners[(i + 1) % corners.len()], theme.hud);
+                }
+                renderer.text(
+                    &Point::new(left + BUTTON_WIDTH / 2.0, bottom + BUTTON_HEIGHT / 2.0),
+                    label,
+                    theme.hud,
+                    "32px myfont",
+                    "center",
+                );
+            }
+        }
+    }
+
+    fn button_at(point: &Point) -> Option<Action> {
+        BUTTONS
+            .iter()
+            .find(|(left, bottom, _, _)| {
+                (*left..=*left + BUTTON_WIDTH).contains(&point.x)
+                    && (*bottom..=*bottom + BUTTON_HEIGHT).contains(&point.y)
+            })
+            .map(|(_, _, _, action)| *action)
+    }
+}
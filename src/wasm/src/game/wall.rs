@@ -1,7 +1,9 @@
 pub mod wall {
     //! wall related functions.
-    use crate::engine::{Point, Velocity};
-    use crate::game::{Piece, Renderer, State, StateMachine};
+    use crate::engine::{Point, Theme, Velocity};
+    use crate::game::{Piece, Renderer, Sprites, State, StateMachine};
+    use crate::geometry::{Rect, RowGrid};
+    use anyhow::{Result, anyhow};
 
     /// Wall represents a line segment boundary in the game world.
     /// Walls are static geometric elements that define collision boundaries.
@@ -50,10 +52,12 @@ pub mod wall {
         /// # Arguments
         /// * `&self` - Reference to the wall instance
         /// * `renderer` - Reference to the renderer for drawing operations
+        /// * `theme` - Colors to draw with
+        /// * `_sprites` - Figures to draw with, unused as walls are lines
         ///
         /// # Returns
         /// Nothing (unit type)
-        fn draw(&self, renderer: &Renderer) {
+        fn draw(&self, renderer: &dyn Renderer, theme: &Theme, _sprites: &Sprites) {
             renderer.line(
                 &Point {
                     x: self.state_machine.context().p.x,
@@ -63,45 +67,110 @@ pub mod wall {
                     x: self.state_machine.context().q.x,
                     y: self.state_machine.context().q.y,
                 },
+                theme.walls,
             );
         }
     }
 
-    /// Static wall data defining the game level geometry.
-    /// Each tuple represents (x1, y1, x2, y2) coordinates for wall line segments.
-    /// The walls form the boundaries and obstacles of the game world.
-    pub const WALLS_DATA: [(f32, f32, f32, f32); 26] = [
-        // left wall
-        (100.0, 0.0, 100.0, 600.0),
-        (100.0, 600.0, 200.0, 1000.0),
-        (200.0, 1000.0, 50.0, 1800.0),
-        (50.0, 1800.0, 400.0, 2500.0),
-        (400.0, 2500.0, 400.0, 3300.0),
-        (400.0, 3300.0, 100.0, 3300.0),
-        (100.0, 3300.0, 100.0, 6500.0),
-        (100.0, 6500.0, 200.0, 6800.0),
-        (200.0, 6800.0, 100.0, 7200.0),
-        (100.0, 7200.0, 100.0, 9500.0),
-        // right wall
-        (700.0, 0.0, 700.0, 600.0),
-        (700.0, 600.0, 350.0, 1800.0),
-        (350.0, 1800.0, 700.0, 2500.0),
-        (700.0, 2500.0, 700.0, 3300.0),
-        (700.0, 3300.0, 700.0, 3300.0),
-        (700.0, 3300.0, 700.0, 6500.0),
-        (700.0, 6500.0, 600.0, 6800.0),
-        (600.0, 6800.0, 700.0, 7200.0),
-        (700.0, 7200.0, 700.0, 9500.0),
-        // left wall in island1
-        (450.0, 4200.0, 250.0, 4800.0),
-        (250.0, 4800.0, 550.0, 5500.0),
-        // right wall in island1
-        (450.0, 4200.0, 600.0, 4800.0),
-        (600.0, 4800.0, 550.0, 5500.0),
-        // left wall in island2
-        (200.0, 5000.0, 200.0, 6150.0),
-        (200.0, 6150.0, 550.0, 6150.0),
-        // right wall in island2
-        (200.0, 5000.0, 550.0, 6150.0),
-    ];
+    /// Height of the stretches of course the walls are indexed by, about a
+    /// third of the screen
+    const TRACK_ROW_HEIGHT: f32 = 300.0;
+
+    ///
+    /// Wall segments of a race course, loaded from a track file.
+    ///
+    /// The file has one segment per line as `x1, y1, x2, y2`. Blank lines and
+    /// anything after `#` are ignored.
+    pub struct Track {
+        segments: Vec<(Point, Point)>,
+        /// Segments by the stretch of the course they cover
+        index: RowGrid,
+    }
+    impl Track {
+        /// Parses a track file
+        ///
+        /// # Arguments
+        /// * `text` - Content of the track file
+        ///
+        /// # Returns
+        /// The track, or an error naming the first line that is not a segment
+        pub fn parse(text: &str) -> Result<Self> {
+            let mut segments = vec![];
+            for (number, line) in text.lines().enumerate() {
+                let line = line.split('#').next().unwrap_or_default().trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let values = line
+                    .split(',')
+                    .map(|value| value.trim().parse::<f32>())
+                    .collect::<Result<Vec<f32>, _>>()
+                    .map_err(|err| anyhow!("line {}: {}", number + 1, err))?;
+                let [x1, y1, x2, y2] = values[..] else {
+                    return Err(anyhow!("line {}: expected `x1, y1, x2, y2`", number + 1));
+                };
+                segments.push((Point { x: x1, y: y1 }, Point { x: x2, y: y2 }));
+            }
+            if segments.is_empty() {
+                return Err(anyhow!("track has no walls"));
+            }
+            let index = RowGrid::new(
+                segments
+                    .iter()
+                    .map(|(p, q)| Rect::around(&[*p, *q]))
+                    .collect(),
+                TRACK_ROW_HEIGHT,
+            );
+            Ok(Track { segments, index })
+        }
+
+        /// Creates a wall for each segment of the track
+        pub fn walls(&self) -> Vec<Wall> {
+            self.segments
+                .iter()
+                .map(|(p, q)| Wall::new(*p, *q, Velocity { x: 0.0, y: 0.0 }))
+                .collect()
+        }
+
+        ///
+        /// Finds the walls near an area, e.g. to test or draw only those
+        ///
+        /// # Arguments
+        /// * `area` - Area to look in
+        ///
+        /// # Returns
+        /// Positions in `walls()` of the walls whose bounds overlap `area`, in ascending order
+        pub fn walls_near(&self, area: &Rect) -> Vec<usize> {
+            self.index.query(area)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn walls_near_finds_the_same_walls_as_a_full_scan() {
+            let text = std::fs::read_to_string(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../../assets/track.txt"
+            ))
+            .unwrap();
+            let track = Track::parse(&text).unwrap();
+            for y in (-600..6000).step_by(70) {
+                let area = Rect {
+                    min: Point::new(100.0, y as f32),
+                    max: Point::new(500.0, y as f32 + 150.0),
+                };
+                let scanned: Vec<usize> = track
+                    .segments
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (p, q))| Rect::around(&[*p, *q]).overlaps(&area))
+                    .map(|(index, _)| index)
+                    .collect();
+                assert_eq!(track.walls_near(&area), scanned, "near y = {}", y);
+            }
+        }
+    }
 }
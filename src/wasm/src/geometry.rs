@@ -0,0 +1,416 @@
+//! Collision geometry on the game plane.
+//!
+//! Every test is closed: shapes that only touch at an edge or an endpoint, or
+//! segments lying along an edge, count as intersecting.
+use crate::engine::{Line, Point, Velocity};
+
+/// Axis-aligned rectangle, e.g. the area a moving shape can reach in one tick
+#[derive(Clone, Copy)]
+pub struct Rect {
+    pub min: Point,
+    pub max: Point,
+}
+impl Rect {
+    /// Smallest rectangle holding every point. Empty input gives a rectangle
+    /// at the origin.
+    pub fn around(points: &[Point]) -> Rect {
+        let first = points.first().copied().unwrap_or_default();
+        points.iter().fold(
+            Rect {
+                min: first,
+                max: first,
+            },
+            |rect, point| Rect {
+                min: Point::new(rect.min.x.min(point.x), rect.min.y.min(point.y)),
+                max: Point::new(rect.max.x.max(point.x), rect.max.y.max(point.y)),
+            },
+        )
+    }
+
+    /// Whether the rectangles share at least one point
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+}
+
+///
+/// A closed box turned to any direction, given by its center, the unit
+/// direction of its length and its half extents.
+#[derive(Clone, Copy)]
+pub struct OrientedBox {
+    center: Point,
+    /// Unit vector along the box's length
+    axis: (f32, f32),
+    /// Half of the extent across the axis
+    half_width: f32,
+    /// Half of the extent along the axis
+    half_length: f32,
+}
+impl OrientedBox {
+    ///
+    /// Creates a box
+    ///
+    /// # Arguments
+    /// * `center` - Middle of the box
+    /// * `direction` - Direction of the box's length, of any non-zero size.
+    ///   A zero direction points the length up.
+    /// * `half_width` - Half of the extent across `direction`
+    /// * `half_length` - Half of the extent along `direction`
+    pub fn new(center: Point, direction: Velocity, half_width: f32, half_length: f32) -> Self {
+        let length = direction.x.hypot(direction.y);
+        let axis = if length > 0.0 {
+            (direction.x / length, direction.y / length)
+        } else {
+            (0.0, 1.0)
+        };
+        OrientedBox {
+            center,
+            axis,
+            half_width: half_width.abs(),
+            half_length: half_length.abs(),
+        }
+    }
+
+    /// The four corners, going around the box
+    pub fn corners(&self) -> [Point; 4] {
+        let (ax, ay) = self.axis;
+        // Across the axis, a quarter turn clockwise
+        let (cx, cy) = (ay, -ax);
+        let corner = |along: f32, across: f32| {
+            Point::new(
+                self.center.x + ax * along * self.half_length + cx * across * self.half_width,
+                self.center.y + ay * along * self.half_length + cy * across * self.half_width,
+            )
+        };
+        [
+            corner(1.0, -1.0),
+            corner(1.0, 1.0),
+            corner(-1.0, 1.0),
+            corner(-1.0, -1.0),
+        ]
+    }
+
+    /// Area the box covers while moving by `movement`
+    pub fn swept_bounds(&self, movement: Velocity) -> Rect {
+        let corners = self.corners();
+        let moved = corners.map(|corner| Point::new(corner.x + movement.x, corner.y + movement.y));
+        Rect::around(&[corners, moved].concat())
+    }
+
+    ///
+    /// Checks if a segment touches the box where it stands
+    ///
+    /// # Arguments
+    /// * `line` - Segment to test, endpoints included
+    ///
+    /// # Returns
+    /// `true` if the segment and the box share at least one point
+    pub fn intersects(&self, line: &Line) -> bool {
+        self.sweep_intersects(line, Velocity::default())
+    }
+
+    ///
+    /// Checks if a segment touches the box anywhere along a straight move
+    ///
+    /// Moving the box by `movement` against a still segment is the same as
+    /// moving the segment by the opposite against a still box. The segment
+    /// sweeps a parallelogram then, which is tested against the box by
+    /// separating axes, so a fast box cannot pass through a thin wall.
+    ///
+    /// # Arguments
+    /// * `line` - Segment to test, endpoints included
+    /// * `movement` - Distance the box moves
+    ///
+    /// # Returns
+    /// `true` if the segment and the box share at least one point at some time in the move
+    pub fn sweep_intersects(&self, line: &Line, movement: Velocity) -> bool {
+        let swept = [
+            line.p,
+            line.q,
+            Point::new(line.q.x - movement.x, line.q.y - movement.y),
+            Point::new(line.p.x - movement.x, line.p.y - movement.y),
+        ];
+        let corners = self.corners();
+        let (ax, ay) = self.axis;
+        let axes = [
+            (ax, ay),
+            (ay, -ax),
+            normal(line.q.x - line.p.x, line.q.y - line.p.y),
+            normal(movement.x, movement.y),
+        ];
+        !axes
+            .iter()
+            .any(|&axis| axis != (0.0, 0.0) && separates(axis, &corners, &swept))
+    }
+}
+
+/// A vector at a right angle to `(x, y)`, zero for a zero vector
+fn normal(x: f32, y: f32) -> (f32, f32) {
+    (-y, x)
+}
+
+/// Whether the shapes' shadows on `axis` leave a gap. Shadows that touch leave none.
+fn separates(axis: (f32, f32), a: &[Point], b: &[Point]) -> bool {
+    let shadow = |points: &[Point]| {
+        points
+            .iter()
+            .map(|point| point.x * axis.0 + point.y * axis.1)
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+                (min.min(value), max.max(value))
+            })
+    };
+    let (a_min, a_max) = shadow(a);
+    let (b_min, b_max) = shadow(b);
+    a_max < b_min || b_max < a_min
+}
+
+///
+/// Index of items by the rows of the plane their bounds cover.
+///
+/// Tracks run up the y axis, so cutting the plane into rows of a fixed height
+/// keeps each query to the few rows around the area asked for.
+pub struct RowGrid {
+    row_height: f32,
+    /// Lowest y of any item, where the first row starts
+    bottom: f32,
+    /// Indices of the items touching each row, in ascending order
+    rows: Vec<Vec<usize>>,
+    bounds: Vec<Rect>,
+}
+impl RowGrid {
+    ///
+    /// Builds the index
+    ///
+    /// # Arguments
+    /// * `bounds` - Bounds of each item, looked up by its position in the list
+    /// * `row_height` - Height of a row. Items much taller span many rows.
+    pub fn new(bounds: Vec<Rect>, row_height: f32) -> Self {
+        let bottom = bounds
+            .iter()
+            .map(|rect| rect.min.y)
+            .fold(f32::INFINITY, f32::min);
+        let mut grid = RowGrid {
+            row_height,
+            bottom: if bottom.is_finite() { bottom } else { 0.0 },
+            rows: vec![],
+            bounds: vec![],
+        };
+        let top = bounds.iter().map(|rect| grid.row(rect.max.y)).max();
+        grid.rows = vec![vec![]; top.map_or(0, |top| top + 1)];
+        for (index, rect) in bounds.iter().enumerate() {
+            for row in grid.row(rect.min.y)..=grid.row(rect.max.y) {
+                grid.rows[row].push(index);
+            }
+        }
+        grid.bounds = bounds;
+        grid
+    }
+
+    ///
+    /// Finds the items near an area
+    ///
+    /// # Arguments
+    /// * `area` - Area to look in
+    ///
+    /// # Returns
+    /// Indices of the items whose bounds overlap `area`, in ascending order
+    pub fn query(&self, area: &Rect) -> Vec<usize> {
+        let Some(last) = self.rows.len().checked_sub(1) else {
+            return vec![];
+        };
+        if area.max.y < self.bottom {
+            return vec![];
+        }
+        let first = self.row(area.min.y);
+        let mut found = vec![];
+        for row in first..=self.row(area.max.y).min(last) {
+            for &index in &self.rows[row] {
+                let bounds = &self.bounds[index];
+                // An item spanning several rows is reported from the first one searched
+                if self.row(bounds.min.y).max(first) == row && bounds.overlaps(area) {
+                    found.push(index);
+                }
+            }
+        }
+        found.sort_unstable();
+        found
+    }
+
+    /// Row holding `y`, the first row for anything below it
+    fn row(&self, y: f32) -> usize {
+        ((y - self.bottom) / self.row_height).floor().max(0.0) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(px: f32, py: f32, qx: f32, qy: f32) -> Line {
+        Line::new(Point::new(px, py), Point::new(qx, qy))
+    }
+
+    /// Upright box from (-10, -20) to (10, 20)
+    fn upright_box() -> OrientedBox {
+        OrientedBox::new(
+            Point::new(0.0, 0.0),
+            Velocity { x: 0.0, y: 1.0 },
+            10.0,
+            20.0,
+        )
+    }
+
+    #[test]
+    fn corners_follow_the_direction() {
+        let turned = OrientedBox::new(
+            Point::new(0.0, 0.0),
+            Velocity { x: 2.0, y: 0.0 },
+            10.0,
+            20.0,
+        );
+        let bounds = Rect::around(&turned.corners());
+        assert_eq!((bounds.min.x, bounds.min.y), (-20.0, -10.0));
+        assert_eq!((bounds.max.x, bounds.max.y), (20.0, 10.0));
+    }
+
+    #[test]
+    fn intersects_segments_crossing_or_inside_the_box() {
+        let shape = upright_box();
+        assert!(shape.intersects(&line(-30.0, 0.0, 30.0, 0.0)));
+        assert!(shape.intersects(&line(-1.0, -1.0, 1.0, 1.0)));
+        assert!(!shape.intersects(&line(-30.0, 25.0, 30.0, 25.0)));
+        // Diagonal passing by a corner
+        assert!(!shape.intersects(&line(5.0, 30.0, 20.0, 15.0)));
+    }
+
+    #[test]
+    fn intersects_segments_that_only_touch() {
+        let shape = upright_box();
+        // Along an edge
+        assert!(shape.intersects(&line(10.0, -50.0, 10.0, 50.0)));
+        // Ending on an edge
+        assert!(shape.intersects(&line(30.0, 0.0, 10.0, 0.0)));
+        // Through a corner only
+        assert!(shape.intersects(&line(0.0, 30.0, 20.0, 10.0)));
+        assert!(!shape.intersects(&line(10.1, -50.0, 10.1, 50.0)));
+    }
+
+    #[test]
+    fn intersects_collinear_segments_only_where_they_overlap_the_box() {
+        let shape = upright_box();
+        assert!(shape.intersects(&line(10.0, 20.0, 10.0, 40.0)));
+        assert!(!shape.intersects(&line(10.0, 21.0, 10.0, 40.0)));
+        assert!(shape.intersects(&line(-10.0, -20.0, 10.0, -20.0)));
+        assert!(!shape.intersects(&line(11.0, -20.0, 30.0, -20.0)));
+    }
+
+    #[test]
+    fn intersects_a_segment_of_zero_length_as_a_point() {
+        let shape = upright_box();
+        assert!(shape.intersects(&line(5.0, 5.0, 5.0, 5.0)));
+        assert!(!shape.intersects(&line(15.0, 5.0, 15.0, 5.0)));
+    }
+
+    #[test]
+    fn sweep_intersects_a_wall_the_box_moves_through() {
+        let shape = upright_box();
+        let wall = line(-50.0, 100.0, 50.0, 100.0);
+        // The box starts and ends clear of the wall
+        let movement = Velocity { x: 0.0, y: 200.0 };
+        let moved = OrientedBox::new(
+            Point::new(0.0, 200.0),
+            Velocity { x: 0.0, y: 1.0 },
+            10.0,
+            20.0,
+        );
+        assert!(!shape.intersects(&wall));
+        assert!(!moved.intersects(&wall));
+        assert!(shape.sweep_intersects(&wall, movement));
+        assert!(!shape.sweep_intersects(&wall, Velocity { x: 0.0, y: 79.0 }));
+        assert!(shape.sweep_intersects(&wall, Velocity { x: 0.0, y: 80.0 }));
+    }
+
+    #[test]
+    fn sweep_intersects_only_walls_along_the_path() {
+        let shape = upright_box();
+        let movement = Velocity { x: 100.0, y: 100.0 };
+        // Crossed by the diagonal move, though neither end of it is near the wall
+        assert!(shape.sweep_intersects(&line(40.0, 60.0, 60.0, 40.0), movement));
+        // Beside the path
+        assert!(!shape.sweep_intersects(&line(60.0, 0.0, 90.0, 0.0), movement));
+        // Behind the start
+        assert!(!shape.sweep_intersects(&line(-50.0, -30.0, 50.0, -30.0), movement));
+    }
+
+    #[test]
+    fn sweep_intersects_a_wall_along_the_move() {
+        let shape = upright_box();
+        let movement = Velocity { x: 0.0, y: 100.0 };
+        assert!(shape.sweep_intersects(&line(10.0, 50.0, 10.0, 60.0), movement));
+        assert!(!shape.sweep_intersects(&line(10.5, 50.0, 10.5, 60.0), movement));
+    }
+
+    fn rect(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Rect {
+        Rect {
+            min: Point::new(min_x, min_y),
+            max: Point::new(max_x, max_y),
+        }
+    }
+
+    /// Rows of 100 from y = 0: a short item in the first row, one spanning
+    /// every row, one in the last row and one on the left of the middle row
+    fn grid() -> RowGrid {
+        RowGrid::new(
+            vec![
+                rect(0.0, 0.0, 100.0, 50.0),
+                rect(0.0, 0.0, 100.0, 500.0),
+                rect(0.0, 450.0, 100.0, 500.0),
+                rect(-200.0, 250.0, -100.0, 260.0),
+            ],
+            100.0,
+        )
+    }
+
+    #[test]
+    fn query_finds_items_overlapping_the_area() {
+        let grid = grid();
+        assert_eq!(grid.query(&rect(0.0, 0.0, 10.0, 10.0)), [0, 1]);
+        assert_eq!(grid.query(&rect(0.0, 460.0, 10.0, 470.0)), [1, 2]);
+        assert_eq!(grid.query(&rect(-150.0, 240.0, -120.0, 300.0)), [3]);
+        // In the rows of item 3, but beside it
+        assert_eq!(
+            grid.query(&rect(-90.0, 240.0, -10.0, 300.0)),
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn query_reports_items_spanning_rows_once() {
+        let grid = grid();
+        assert_eq!(grid.query(&rect(-500.0, 0.0, 500.0, 500.0)), [0, 1, 2, 3]);
+        assert_eq!(grid.query(&rect(50.0, 120.0, 60.0, 380.0)), [1]);
+    }
+
+    #[test]
+    fn query_outside_the_grid_finds_what_reaches_into_it() {
+        let grid = grid();
+        assert!(grid.query(&rect(0.0, -100.0, 10.0, -1.0)).is_empty());
+        assert!(grid.query(&rect(0.0, 501.0, 10.0, 900.0)).is_empty());
+        // Reaching in from below and above the rows
+        assert_eq!(grid.query(&rect(0.0, -100.0, 10.0, 0.0)), [0, 1]);
+        assert_eq!(grid.query(&rect(0.0, 500.0, 10.0, 900.0)), [1, 2]);
+        assert_eq!(
+            grid.query(&rect(-500.0, -500.0, 500.0, 900.0)),
+            [0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn query_on_an_empty_grid_finds_nothing() {
+        let grid = RowGrid::new(vec![], 100.0);
+        assert!(grid.query(&rect(-10.0, -10.0, 10.0, 10.0)).is_empty());
+    }
+}
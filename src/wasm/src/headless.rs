@@ -0,0 +1,232 @@
+//! Platform backends for running the game without a browser.
+use crate::engine::{
+    ActionState, AssetLoader, Audio, Bus, EngineSound, Input, KeyBindingStore, KeyBindings,
+    KeyState, MixerSettings, Playback, Sound,
+};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use std::{
+    cell::{Cell, RefCell},
+    path::PathBuf,
+};
+
+/// Audio backend that hands out sound handles and plays nothing. The browser
+/// falls back to it when it cannot play sound.
+#[derive(Default)]
+pub struct SilentAudio {
+    loaded: Cell<usize>,
+    mixer: Cell<MixerSettings>,
+}
+
+#[async_trait(?Send)]
+impl Audio for SilentAudio {
+    async fn load_sound(&self, _filename: &str, bus: Bus) -> Result<Sound> {
+        let id = self.loaded.get();
+        self.loaded.set(id + 1);
+        Ok(Sound { id, bus })
+    }
+    fn play_sound(&self, _sound: &Sound) -> Result<Box<dyn Playback>> {
+        Ok(Box::new(SilentPlayback {
+            playing: Cell::new(false),
+        }))
+    }
+    fn play_looping_sound(&self, _sound: &Sound) -> Result<Box<dyn Playback>> {
+        Ok(Box::new(SilentPlayback {
+            playing: Cell::new(true),
+        }))
+    }
+    fn play_engine_sound(&self) -> Result<Box<dyn EngineSound>> {
+        Ok(Box::new(SilentEngineSound))
+    }
+    fn suspend(&self) -> Result<()> {
+        Ok(())
+    }
+    fn resume(&self) -> Result<()> {
+        Ok(())
+    }
+    fn mixer(&self) -> MixerSettings {
+        self.mixer.get()
+    }
+    fn set_mixer(&self, settings: MixerSettings) -> Result<()> {
+        self.mixer.set(settings);
+        Ok(())
+    }
+    fn is_available(&self) -> bool {
+        false
+    }
+}
+
+/// Key bindings kept in memory only, starting from the default ones
+#[derive(Default)]
+pub struct MemoryKeyBindings {
+    bindings: RefCell<KeyBindings>,
+}
+
+impl KeyBindingStore for MemoryKeyBindings {
+    fn key_bindings(&self) -> KeyBindings {
+        self.bindings.borrow().clone()
+    }
+    fn set_key_bindings(&self, bindings: KeyBindings) -> Result<()> {
+        self.bindings.replace(bindings);
+        Ok(())
+    }
+}
+
+/// Handle to a sound played by `SilentAudio`. One-shot sounds end right away,
+/// looping sounds play until they are stopped.
+struct SilentPlayback {
+    playing: Cell<bool>,
+}
+
+impl Playback for SilentPlayback {
+    fn stop(&self) -> Result<()> {
+        self.playing.set(false);
+        Ok(())
+    }
+    fn fade_out(&self, _seconds: f64) -> Result<()> {
+        self.playing.set(false);
+        Ok(())
+    }
+    fn set_playback_rate(&self, _rate: f32) -> Result<()> {
+        Ok(())
+    }
+    fn set_looping(&self, looping: bool) -> Result<()> {
+        if !looping {
+            self.playing.set(false);
+        }
+        Ok(())
+    }
+    fn set_pan(&self, _pan: f32) -> Result<()> {
+        Ok(())
+    }
+    fn is_playing(&self) -> bool {
+        self.playing.get()
+    }
+}
+
+/// Handle to an engine drone played by `SilentAudio`
+struct SilentEngineSound;
+
+impl EngineSound for SilentEngineSound {
+    fn set_speed(&self, _speed: f32) -> Result<()> {
+        Ok(())
+    }
+    fn fade_out(&self, _seconds: f64) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Asset loader that reads files from a directory standing in for the asset base URL.
+/// Fonts are only needed to draw, so they are not loaded.
+pub struct FileAssets {
+    root: PathBuf,
+}
+
+impl FileAssets {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FileAssets { root: root.into() }
+    }
+}
+
+#[async_trait(?Send)]
+impl AssetLoader for FileAssets {
+    fn locate(&self, path: &str) -> String {
+        self.root.join(path).to_string_lossy().into_owned()
+    }
+    async fn load_font(&self, _family: &str, _url: &str) -> Result<()> {
+        Ok(())
+    }
+    async fn load_text(&self, url: &str) -> Result<String> {
+        std::fs::read_to_string(url).map_err(|err| anyhow!("could not read {}: {}", url, err))
+    }
+}
+
+struct ScriptEvent {
+    tick: u32,
+    pressed: bool,
+    code: String,
+}
+
+///
+/// Key presses and releases scheduled by simulation tick.
+///
+/// The script has one event per line: `<tick> <down|up> <code>`, where `code`
+/// is a `KeyboardEvent.code` such as `ArrowUp` or `Space`. Keys are mapped to
+/// actions with the default `KeyBindings`. Blank lines and anything after `#`
+/// are ignored.
+///
+/// ```text
+/// 0   down Space
+/// 1   up   Space
+/// 1   down ArrowUp
+/// 300 down ArrowLeft
+/// 360 up   ArrowLeft
+/// ```
+pub struct ScriptedInput {
+    events: Vec<ScriptEvent>,
+    next: usize,
+    tick: u32,
+    keystate: KeyState,
+    bindings: KeyBindings,
+}
+
+impl ScriptedInput {
+    pub fn parse(script: &str) -> Result<Self> {
+        let mut events = vec![];
+        for (number, line) in script.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [tick, action, code] = fields[..] else {
+                return Err(anyhow!(
+                    "line {}: expected `<tick> <down|up> <code>`",
+                    number + 1
+                ));
+            };
+            let tick = tick
+                .parse::<u32>()
+                .map_err(|err| anyhow!("line {}: invalid tick {:?}: {}", number + 1, tick, err))?;
+            let pressed = match action {
+                "down" => true,
+                "up" => false,
+                _ => return Err(anyhow!("line {}: unknown action {:?}", number + 1, action)),
+            };
+            events.push(ScriptEvent {
+                tick,
+                pressed,
+                code: code.to_string(),
+            });
+        }
+        events.sort_by_key(|event| event.tick);
+        Ok(ScriptedInput {
+            events,
+            next: 0,
+            tick: 0,
+            keystate: KeyState::new(),
+            bindings: KeyBindings::default(),
+        })
+    }
+}
+
+impl Input for ScriptedInput {
+    fn process_input(&mut self, actions: &mut ActionState) {
+        while let Some(event) = self.events.get(self.next) {
+            if event.tick > self.tick {
+                break;
+            }
+            if event.pressed {
+                if !self.keystate.is_pressed(&event.code) {
+                    actions.set_key(&event.code);
+                }
+                self.keystate.set_pressed(&event.code);
+            } else {
+                self.keystate.set_released(&event.code);
+            }
+            self.next += 1;
+        }
+        self.tick += 1;
+        self.bindings.apply(&self.keystate, actions);
+    }
+}
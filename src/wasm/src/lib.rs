@@ -1,21 +1,38 @@
+#![allow(clippy::module_inception)]
 #[macro_use]
 mod browser;
-mod engine;
-mod game;
+pub mod engine;
+pub mod game;
+pub mod geometry;
+pub mod headless;
 mod sound;
+mod web;
 
-use engine::GameLoop;
+use engine::Audio;
 use game::GameStage;
+use headless::SilentAudio;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
+use web::{GameLoop, SavedKeyBindings, WebAssets, WebAudio};
 
 #[wasm_bindgen]
 pub fn main() -> Result<(), JsValue> {
     console_error_panic_hook::set_once();
 
     browser::spawn_local(async move {
-        let game = GameStage::new();
+        // Play without sound rather than not at all
+        let audio: Rc<dyn Audio> = match WebAudio::new() {
+            Ok(audio) => Rc::new(audio),
+            Err(err) => {
+                log!("Could not create audio context {:#?}", err);
+                Rc::new(SilentAudio::default())
+            }
+        };
+        let assets = WebAssets::new().expect("Could not find the asset base URL");
+        let key_bindings = Rc::new(SavedKeyBindings::new());
+        let game = GameStage::new(audio, Rc::new(assets), key_bindings.clone());
 
-        GameLoop::start(game)
+        GameLoop::start(game, key_bindings)
             .await
             .expect("Cloud not start game loop");
     });
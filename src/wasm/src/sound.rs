@@ -1,8 +1,14 @@
+use crate::engine::{Bus, MixerSettings};
 use anyhow::{Result, anyhow};
 use js_sys::ArrayBuffer;
-use wasm_bindgen::JsCast;
+use std::{cell::Cell, rc::Rc};
+use wasm_bindgen::{JsCast, prelude::Closure};
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{AudioBuffer, AudioBufferSourceNode, AudioContext, AudioDestinationNode, AudioNode};
+use web_sys::{
+    AudioBuffer, AudioBufferSourceNode, AudioContext, AudioContextState, AudioNode, AudioParam,
+    AudioScheduledSourceNode, BiquadFilterNode, BiquadFilterType, GainNode, OscillatorNode,
+    OscillatorType, StereoPannerNode,
+};
 
 //
 pub fn create_audio_context() -> Result<AudioContext> {
@@ -14,36 +20,274 @@ fn create_buffer_source(ctx: &AudioContext) -> Result<AudioBufferSourceNode> {
         .map_err(|err| anyhow!("Error creating buffer source {:#?}", err))
 }
 
-fn connect_with_audio_node(
-    buffer_source: &AudioBufferSourceNode,
-    destination: &AudioDestinationNode,
-) -> Result<AudioNode> {
-    buffer_source
+fn connect_with_audio_node(source: &AudioNode, destination: &AudioNode) -> Result<AudioNode> {
+    source
         .connect_with_audio_node(destination)
         .map_err(|err| anyhow!("Error connecting audio source to destination {:#?}", err))
 }
 
-fn create_track_source(ctx: &AudioContext, buffer: &AudioBuffer) -> Result<AudioBufferSourceNode> {
+fn create_gain(ctx: &AudioContext) -> Result<GainNode> {
+    ctx.create_gain()
+        .map_err(|err| anyhow!("Error creating gain node {:#?}", err))
+}
+
+fn create_stereo_panner(ctx: &AudioContext) -> Result<StereoPannerNode> {
+    ctx.create_stereo_panner()
+        .map_err(|err| anyhow!("Error creating stereo panner {:#?}", err))
+}
+
+fn create_track_source(
+    ctx: &AudioContext,
+    buffer: &AudioBuffer,
+    destination: &AudioNode,
+) -> Result<AudioBufferSourceNode> {
     let track_source = create_buffer_source(ctx)?;
     track_source.set_buffer(Some(buffer));
-    connect_with_audio_node(&track_source, &ctx.destination())?;
+    connect_with_audio_node(&track_source, destination)?;
     Ok(track_source)
 }
 
+/// Gain nodes every sound plays through: a bus each for music and effects,
+/// both feeding a master bus connected to the speakers.
+#[derive(Clone)]
+pub struct Mixer {
+    master: GainNode,
+    music: GainNode,
+    effects: GainNode,
+}
+
+impl Mixer {
+    pub fn new(ctx: &AudioContext) -> Result<Self> {
+        let mixer = Mixer {
+            master: create_gain(ctx)?,
+            music: create_gain(ctx)?,
+            effects: create_gain(ctx)?,
+        };
+        connect_with_audio_node(&mixer.master, &ctx.destination())?;
+        connect_with_audio_node(&mixer.music, &mixer.master)?;
+        connect_with_audio_node(&mixer.effects, &mixer.master)?;
+        Ok(mixer)
+    }
+
+    /// Node the sounds of `bus` connect to
+    pub fn bus(&self, bus: Bus) -> &GainNode {
+        match bus {
+            Bus::Master => &self.master,
+            Bus::Music => &self.music,
+            Bus::Effects => &self.effects,
+        }
+    }
+
+    /// Sets the gain of every bus
+    pub fn apply(&self, settings: &MixerSettings) {
+        for bus in [Bus::Master, Bus::Music, Bus::Effects] {
+            self.bus(bus).gain().set_value(settings.gain(bus));
+        }
+    }
+}
+
 pub enum Looping {
     No,
     Yes,
 }
 
-pub fn play_sound(ctx: &AudioContext, buffer: &AudioBuffer, looping: Looping) -> Result<()> {
-    let track_source = create_track_source(ctx, buffer)?;
+/// A playing sound, with a panner and a gain node of its own so it can be
+/// placed and faded out alone
+pub struct Voice {
+    ctx: AudioContext,
+    source: AudioBufferSourceNode,
+    panner: StereoPannerNode,
+    gain: GainNode,
+    /// Cleared by the source when it ends
+    playing: Rc<Cell<bool>>,
+}
+
+impl Voice {
+    pub fn stop(&self) -> Result<()> {
+        AudioScheduledSourceNode::stop(&self.source)
+            .map_err(|err| anyhow!("Could not stop sound {:#?}", err))
+    }
+
+    /// Ramps the gain down to silence over `seconds` and stops the sound at the end
+    pub fn fade_out(&self, seconds: f64) -> Result<()> {
+        let now = self.ctx.current_time();
+        let gain = self.gain.gain();
+        gain.cancel_scheduled_values(now)
+            .and_then(|gain| gain.set_value_at_time(gain.value(), now))
+            .and_then(|gain| gain.linear_ramp_to_value_at_time(0.0, now + seconds))
+            .map_err(|err| anyhow!("Could not fade out sound {:#?}", err))?;
+        AudioScheduledSourceNode::stop_with_when(&self.source, now + seconds)
+            .map_err(|err| anyhow!("Could not stop sound {:#?}", err))
+    }
+
+    pub fn set_playback_rate(&self, rate: f32) {
+        self.source.playback_rate().set_value(rate);
+    }
+
+    pub fn set_looping(&self, looping: bool) {
+        self.source.set_loop(looping);
+    }
+
+    pub fn set_pan(&self, pan: f32) {
+        self.panner.pan().set_value(pan.clamp(-1.0, 1.0));
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing.get()
+    }
+}
+
+pub fn play_sound(
+    ctx: &AudioContext,
+    buffer: &AudioBuffer,
+    destination: &AudioNode,
+    looping: Looping,
+) -> Result<Voice> {
+    let gain = create_gain(ctx)?;
+    connect_with_audio_node(&gain, destination)?;
+    let panner = create_stereo_panner(ctx)?;
+    connect_with_audio_node(&panner, &gain)?;
+    let track_source = create_track_source(ctx, buffer, &panner)?;
     if matches!(looping, Looping::Yes) {
         track_source.set_loop(true);
     }
+    let playing = Rc::new(Cell::new(true));
+    let ended = playing.clone();
+    // The closure frees itself after the one call
+    let onended = Closure::once_into_js(move || ended.set(false));
+    AudioScheduledSourceNode::set_onended(&track_source, Some(onended.unchecked_ref()));
 
     track_source
         .start()
-        .map_err(|err| anyhow!("Could not start sound! {:#?}", err))
+        .map_err(|err| anyhow!("Could not start sound! {:#?}", err))?;
+    Ok(Voice {
+        ctx: ctx.clone(),
+        source: track_source,
+        panner,
+        gain,
+        playing,
+    })
+}
+
+/// Pitch of the engine drone when idling and at full speed, in Hz
+const ENGINE_PITCH: (f32, f32) = (40.0, 140.0);
+/// Cutoff of the filter taking the edge off the drone, opening up with speed
+const ENGINE_CUTOFF: (f32, f32) = (300.0, 1800.0);
+/// Volume of the drone, so that idling is a murmur under the music
+const ENGINE_GAIN: (f32, f32) = (0.05, 0.25);
+/// Cents the second oscillator is detuned by, to make the drone beat
+const ENGINE_DETUNE: f32 = 12.0;
+/// Seconds a change of speed takes to mostly settle, so the drone glides
+const ENGINE_GLIDE: f64 = 0.05;
+
+/// A synthesized engine drone: two detuned sawtooth oscillators and a square
+/// one an octave below, through a low-pass filter and a gain node of their own
+pub struct EngineDrone {
+    ctx: AudioContext,
+    oscillators: Vec<OscillatorNode>,
+    filter: BiquadFilterNode,
+    gain: GainNode,
+}
+
+impl EngineDrone {
+    /// Glides the pitch, cutoff and volume to `speed`, 0 idling and 1 at full speed
+    pub fn set_speed(&self, speed: f32) -> Result<()> {
+        let speed = speed.clamp(0.0, 1.0);
+        let at = |(low, high): (f32, f32)| low + (high - low) * speed;
+        let pitch = at(ENGINE_PITCH);
+        self.glide(&self.oscillators[0].frequency(), pitch)?;
+        self.glide(&self.oscillators[1].frequency(), pitch)?;
+        self.glide(&self.oscillators[2].frequency(), pitch / 2.0)?;
+        self.glide(&self.filter.frequency(), at(ENGINE_CUTOFF))?;
+        self.glide(&self.gain.gain(), at(ENGINE_GAIN))
+    }
+
+    /// Ramps the gain down to silence over `seconds` and stops the drone at the end
+    pub fn fade_out(&self, seconds: f64) -> Result<()> {
+        let now = self.ctx.current_time();
+        let gain = self.gain.gain();
+        gain.cancel_scheduled_values(now)
+            .and_then(|gain| gain.set_value_at_time(gain.value(), now))
+            .and_then(|gain| gain.linear_ramp_to_value_at_time(0.0, now + seconds))
+            .map_err(|err| anyhow!("Could not fade out engine sound {:#?}", err))?;
+        for oscillator in &self.oscillators {
+            AudioScheduledSourceNode::stop_with_when(oscillator, now + seconds)
+                .map_err(|err| anyhow!("Could not stop engine sound {:#?}", err))?;
+        }
+        Ok(())
+    }
+
+    fn glide(&self, param: &AudioParam, value: f32) -> Result<()> {
+        param
+            .set_target_at_time(value, self.ctx.current_time(), ENGINE_GLIDE)
+            .map(|_| ())
+            .map_err(|err| anyhow!("Could not change engine sound {:#?}", err))
+    }
+}
+
+fn create_oscillator(
+    ctx: &AudioContext,
+    kind: OscillatorType,
+    frequency: f32,
+    destination: &AudioNode,
+) -> Result<OscillatorNode> {
+    let oscillator = ctx
+        .create_oscillator()
+        .map_err(|err| anyhow!("Error creating oscillator {:#?}", err))?;
+    oscillator.set_type(kind);
+    oscillator.frequency().set_value(frequency);
+    connect_with_audio_node(&oscillator, destination)?;
+    Ok(oscillator)
+}
+
+/// Starts an engine drone idling on `destination`
+pub fn play_engine(ctx: &AudioContext, destination: &AudioNode) -> Result<EngineDrone> {
+    let gain = create_gain(ctx)?;
+    gain.gain().set_value(ENGINE_GAIN.0);
+    connect_with_audio_node(&gain, destination)?;
+    let filter = ctx
+        .create_biquad_filter()
+        .map_err(|err| anyhow!("Error creating filter {:#?}", err))?;
+    filter.set_type(BiquadFilterType::Lowpass);
+    filter.frequency().set_value(ENGINE_CUTOFF.0);
+    connect_with_audio_node(&filter, &gain)?;
+
+    let pitch = ENGINE_PITCH.0;
+    let oscillators = vec![
+        create_oscillator(ctx, OscillatorType::Sawtooth, pitch, &filter)?,
+        create_oscillator(ctx, OscillatorType::Sawtooth, pitch, &filter)?,
+        create_oscillator(ctx, OscillatorType::Square, pitch / 2.0, &filter)?,
+    ];
+    oscillators[1].detune().set_value(ENGINE_DETUNE);
+    for oscillator in &oscillators {
+        AudioScheduledSourceNode::start(oscillator)
+            .map_err(|err| anyhow!("Could not start engine sound {:#?}", err))?;
+    }
+    Ok(EngineDrone {
+        ctx: ctx.clone(),
+        oscillators,
+        filter,
+        gain,
+    })
+}
+
+/// Whether the context plays sound rather than waiting for a user gesture or `resume`
+pub fn is_running(ctx: &AudioContext) -> bool {
+    ctx.state() == AudioContextState::Running
+}
+
+/// Pauses every sound on the context. The returned promise is not awaited.
+pub fn suspend(ctx: &AudioContext) -> Result<()> {
+    ctx.suspend()
+        .map(|_| ())
+        .map_err(|err| anyhow!("Could not suspend audio context {:#?}", err))
+}
+
+/// Restarts sounds paused by `suspend`
+pub fn resume(ctx: &AudioContext) -> Result<()> {
+    ctx.resume()
+        .map(|_| ())
+        .map_err(|err| anyhow!("Could not resume audio context {:#?}", err))
 }
 
 pub async fn decode_audio_data(
@@ -0,0 +1,879 @@
+use crate::browser::{self, LoopClosure};
+use crate::engine::{
+    Action, ActionState, AssetLoader, Audio, Bus, EngineSound, FRAME_SIZE, Game, Input,
+    KeyBindingStore, KeyBindings, KeyState, LoadProgress, MixerSettings, Playback, Point, Renderer,
+    Sound, Sprite, Theme,
+};
+use crate::game::{CANVAS_HEIGHT, CANVAS_WIDTH, TouchControls};
+use crate::sound;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use futures::channel::mpsc::{UnboundedReceiver, unbounded};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+use wasm_bindgen::JsCast;
+use web_sys::CanvasRenderingContext2d;
+use web_sys::{AudioBuffer, AudioContext};
+
+pub struct CanvasRenderer {
+    context: CanvasRenderingContext2d,
+}
+
+impl Renderer for CanvasRenderer {
+    fn clear(&self, point: &Point, width: f32, height: f32, color: &str) {
+        self.context.set_fill_style_str(color);
+        self.context.fill_rect(
+            point.x.into(),
+            CANVAS_HEIGHT as f64 - point.y as f64 - height as f64,
+            width as f64,
+            height as f64,
+        );
+    }
+    fn text(&self, point: &Point, text: &str, color: &str, font: &str, align: &str) {
+        self.context.set_fill_style_str(color);
+        self.context.set_text_align(align);
+        self.context.set_text_baseline("middle");
+        self.context.set_font(font);
+        let _ = self
+            .context
+            .fill_text(text, point.x as f64, CANVAS_HEIGHT as f64 - point.y as f64);
+    }
+    fn line(&self, p: &Point, q: &Point, color: &str) {
+        self.context.begin_path();
+        self.context.set_stroke_style_str(color);
+        self.context
+            .move_to(p.x.into(), CANVAS_HEIGHT as f64 - p.y as f64);
+        self.context
+            .line_to(q.x.into(), CANVAS_HEIGHT as f64 - q.y as f64);
+        self.context.close_path();
+        self.context.stroke();
+    }
+    fn sprite(&self, point: &Point, sprite: &Sprite, color: &str) {
+        self.context.set_text_align("left");
+        self.context.set_text_baseline("middle");
+        self.context.set_font(sprite.font());
+        let width = |text: &str| {
+            self.context
+                .measure_text(text)
+                .map(|metrics| metrics.width())
+                .unwrap_or_default()
+        };
+        for index in 0..sprite.len() {
+            let row = sprite.row_position(point, index);
+            let runs = sprite.runs(index, color);
+            // Lay the runs out from the left edge of the centered row
+            let mut x = row.x as f64 - runs.iter().map(|(text, _)| width(text)).sum::<f64>() / 2.0;
+            for (text, color) in runs {
+                self.context.set_fill_style_str(color);
+                let _ = self
+                    .context
+                    .fill_text(&text, x, CANVAS_HEIGHT as f64 - row.y as f64);
+                x += width(&text);
+            }
+        }
+    }
+}
+
+/// Room left around the canvas for its border, in CSS pixels
+const CANVAS_MARGIN: f64 = 4.0;
+
+///
+/// Sizes the canvas to the largest box with the game's aspect ratio that fits
+/// the window. The space left over is letterboxed by the page background.
+///
+/// The backing store is scaled by `devicePixelRatio` so that text stays sharp
+/// on HiDPI screens, and the context is scaled to match so the game keeps
+/// drawing in `CANVAS_WIDTH` x `CANVAS_HEIGHT` logical units.
+fn fit_canvas(context: &CanvasRenderingContext2d) -> Result<()> {
+    let canvas = browser::canvas()?;
+    let (width, height) = browser::window_size()?;
+    let scale = ((width - CANVAS_MARGIN) / CANVAS_WIDTH as f64)
+        .min((height - CANVAS_MARGIN) / CANVAS_HEIGHT as f64)
+        .max(0.1);
+    let ratio = browser::window()?.device_pixel_ratio();
+
+    let style = canvas.style();
+    style
+        .set_property("width", &format!("{}px", CANVAS_WIDTH as f64 * scale))
+        .and_then(|_| style.set_property("height", &format!("{}px", CANVAS_HEIGHT as f64 * scale)))
+        .map_err(|err| anyhow!("Could not size canvas {:#?}", err))?;
+    // Resizing the backing store resets the context, so the transform comes after it
+    canvas.set_width((CANVAS_WIDTH as f64 * scale * ratio).round() as u32);
+    canvas.set_height((CANVAS_HEIGHT as f64 * scale * ratio).round() as u32);
+    context
+        .set_transform(scale * ratio, 0.0, 0.0, scale * ratio, 0.0, 0.0)
+        .map_err(|err| anyhow!("Could not scale canvas context {:#?}", err))
+}
+
+/// Fits the canvas to the window now and whenever the window is resized,
+/// which includes moving it to a screen with another pixel ratio
+fn prepare_resize(context: &CanvasRenderingContext2d) -> Result<()> {
+    fit_canvas(context)?;
+    let handler_context = context.clone();
+    let onresize = browser::closure_wrap(Box::new(move || {
+        if let Err(err) = fit_canvas(&handler_context) {
+            log!("Error resizing canvas {:#?}", err);
+        }
+    }) as Box<dyn FnMut()>);
+
+    browser::window()?.set_onresize(Some(onresize.as_ref().unchecked_ref()));
+    onresize.forget();
+
+    Ok(())
+}
+
+/// Longest stretch of wall-clock time caught up in one frame. Anything beyond
+/// this (a hidden tab, a long stall) is dropped instead of simulated in a burst.
+const MAX_FRAME_DELTA: f64 = FRAME_SIZE * 10.0;
+
+pub struct GameLoop {
+    last_frame: f64,
+    accumulated_delta: f64,
+}
+type SharedLoopClosure = Rc<RefCell<Option<LoopClosure>>>;
+/// The game once it has loaded, shared by the frame loop and the page event handlers
+type SharedGame = Rc<RefCell<Option<Box<dyn Game>>>>;
+
+impl GameLoop {
+    ///
+    /// Starts the frame loop and loads the game in the background.
+    ///
+    /// Until `Game::initialize` finishes, each frame draws the game's loading
+    /// screen. If loading fails the screen stays up with the errors on it.
+    pub async fn start(
+        game: impl Game + 'static,
+        key_bindings: Rc<SavedKeyBindings>,
+    ) -> Result<()> {
+        let mut inputs: Vec<Box<dyn Input>> = vec![
+            Box::new(Keyboard::new(key_bindings)?),
+            Box::new(Gamepads::default()),
+            Box::new(Touchscreen::new()?),
+        ];
+        let mut game_loop = GameLoop {
+            last_frame: browser::now()?,
+            accumulated_delta: 0.0,
+        };
+
+        let renderer = CanvasRenderer {
+            context: browser::context()?,
+        };
+        prepare_resize(&renderer.context)?;
+
+        let loader = Rc::new(game);
+        let progress = Rc::new(RefCell::new(LoadProgress::default()));
+        let loaded: SharedGame = Rc::new(RefCell::new(None));
+        prepare_visibility(loaded.clone())?;
+        {
+            let (loader, progress, loaded) = (loader.clone(), progress.clone(), loaded.clone());
+            browser::spawn_local(async move {
+                match loader.initialize(&progress).await {
+                    Ok(game) => {
+                        loaded.replace(Some(game));
+                    }
+                    Err(err) => {
+                        log!("Error loading game {:#?}", err);
+                        progress.borrow_mut().fail(format!("{:#}", err));
+                    }
+                }
+            });
+        }
+
+        let f: SharedLoopClosure = Rc::new(RefCell::new(None));
+        let g = f.clone();
+
+        *g.borrow_mut() = Some(browser::create_raf_closure(move |perf: f64| {
+            match loaded.borrow_mut().as_mut() {
+                None => {
+                    loader.draw_loading(&renderer, &progress.borrow());
+                    // Start the race clock from the first frame after loading
+                    game_loop.last_frame = perf;
+                }
+                Some(game) => {
+                    game_loop.accumulated_delta +=
+                        (perf - game_loop.last_frame).min(MAX_FRAME_DELTA);
+                    while game_loop.accumulated_delta > FRAME_SIZE {
+                        // Inputs are polled per tick, not per frame, so a one-tick pulse
+                        // (a released stick, a new touch) is never sampled on a frame
+                        // that runs no tick and lost
+                        let mut actions = ActionState::new();
+                        inputs
+                            .iter_mut()
+                            .for_each(|input| input.process_input(&mut actions));
+                        game.update(&actions);
+                        game_loop.accumulated_delta -= FRAME_SIZE;
+                    }
+                    let _last_frame = browser::now().unwrap();
+                    game.draw(&renderer);
+                    inputs
+                        .iter()
+                        .for_each(|input| input.draw(&renderer, game.theme()));
+                    game_loop.last_frame = _last_frame;
+                }
+            }
+
+            let _ = browser::request_animation_frame(f.borrow().as_ref().unwrap());
+        }));
+
+        browser::request_animation_frame(
+            g.borrow()
+                .as_ref()
+                .ok_or_else(|| anyhow!("GameLoop: Loop is None"))?,
+        )?;
+        Ok(())
+    }
+}
+
+/// Local storage key holding the player's key bindings
+const KEY_BINDINGS_ITEM: &str = "cart.key_bindings";
+
+///
+/// Loads the player's key bindings.
+///
+/// A `?keys=<preset>` query parameter picks one of the `KEY_BINDING_PRESETS`
+/// and saves it. Otherwise the saved bindings are used, falling back to the
+/// default arrow keys.
+pub fn load_key_bindings() -> KeyBindings {
+    if let Ok(Some(preset)) = browser::query_param("keys") {
+        match KeyBindings::preset(&preset) {
+            Some(bindings) => {
+                save_key_bindings(&bindings);
+                return bindings;
+            }
+            None => {
+                log!("Unknown key binding preset {:?}", preset);
+            }
+        }
+    }
+    match browser::load_item(KEY_BINDINGS_ITEM) {
+        Ok(Some(text)) => KeyBindings::decode(&text).unwrap_or_else(|err| {
+            log!("Error loading key bindings {:#?}", err);
+            KeyBindings::default()
+        }),
+        _ => KeyBindings::default(),
+    }
+}
+
+/// Saves the player's key bindings to local storage
+pub fn save_key_bindings(bindings: &KeyBindings) {
+    if let Err(err) = browser::save_item(KEY_BINDINGS_ITEM, &bindings.encode()) {
+        log!("Error saving key bindings {:#?}", err);
+    }
+}
+
+/// The player's key bindings, shared by the keyboard and the controls screen
+/// and saved to local storage whenever they change
+pub struct SavedKeyBindings {
+    bindings: RefCell<KeyBindings>,
+}
+
+impl SavedKeyBindings {
+    /// Starts from the bindings given by `load_key_bindings`
+    pub fn new() -> Self {
+        SavedKeyBindings {
+            bindings: RefCell::new(load_key_bindings()),
+        }
+    }
+}
+
+impl KeyBindingStore for SavedKeyBindings {
+    fn key_bindings(&self) -> KeyBindings {
+        self.bindings.borrow().clone()
+    }
+
+    fn set_key_bindings(&self, bindings: KeyBindings) -> Result<()> {
+        save_key_bindings(&bindings);
+        self.bindings.replace(bindings);
+        Ok(())
+    }
+}
+
+enum KeyPress {
+    KeyUp(web_sys::KeyboardEvent),
+    KeyDown(web_sys::KeyboardEvent),
+}
+
+/// Keyboard input attached to the canvas, mapped to actions by key bindings.
+pub struct Keyboard {
+    keyevent_receiver: UnboundedReceiver<KeyPress>,
+    keystate: KeyState,
+    bindings: Rc<SavedKeyBindings>,
+}
+
+impl Keyboard {
+    pub fn new(bindings: Rc<SavedKeyBindings>) -> Result<Self> {
+        Ok(Keyboard {
+            keyevent_receiver: prepare_input()?,
+            keystate: KeyState::new(),
+            bindings,
+        })
+    }
+}
+
+impl Input for Keyboard {
+    fn process_input(&mut self, actions: &mut ActionState) {
+        loop {
+            match self.keyevent_receiver.try_next() {
+                Ok(None) => break,
+                Err(_err) => break,
+                Ok(Some(evt)) => match evt {
+                    KeyPress::KeyUp(evt) => self.keystate.set_released(&evt.code()),
+                    KeyPress::KeyDown(evt) => {
+                        // Held keys repeat their keydown, which is no new press
+                        if !self.keystate.is_pressed(&evt.code()) {
+                            actions.set_key(&evt.code());
+                        }
+                        self.keystate.set_pressed(&evt.code());
+                    }
+                },
+            };
+        }
+        self.bindings
+            .bindings
+            .borrow()
+            .apply(&self.keystate, actions);
+    }
+}
+
+/// Stick travel ignored around the center, where worn sticks rest
+const GAMEPAD_DEAD_ZONE: f64 = 0.2;
+
+/// Buttons of the standard gamepad mapping and the actions they hold. Triggers
+/// report how far they are pulled, the rest are either pressed or not.
+const GAMEPAD_BUTTONS: [(u32, Action); 13] = [
+    (0, Action::Confirm),     // A / Cross
+    (1, Action::Straighten),  // B / Circle
+    (2, Action::NextTheme),   // X / Square
+    (3, Action::WatchReplay), // Y / Triangle
+    (6, Action::Brake),       // left trigger
+    (7, Action::Accelerate),  // right trigger
+    (9, Action::Pause),       // Start
+    (12, Action::MenuUp),     // d-pad up
+    (13, Action::Straighten), // d-pad down
+    (13, Action::MenuDown),   // d-pad down
+    (14, Action::SteerLeft),  // d-pad left
+    (15, Action::SteerRight), // d-pad right
+    (8, Action::Mute),        // Back / Select
+];
+
+///
+/// Gamepads polled through the Gamepad API.
+///
+/// The left stick steers in proportion to how far it is pushed, and
+/// straightens the cart when it returns to the center.
+#[derive(Default)]
+pub struct Gamepads {
+    steering: bool,
+}
+
+impl Input for Gamepads {
+    fn process_input(&mut self, actions: &mut ActionState) {
+        let Ok(gamepads) = browser::gamepads() else {
+            return;
+        };
+        let mut steering = false;
+        for gamepad in gamepads.iter() {
+            let Ok(gamepad) = gamepad.dyn_into::<web_sys::Gamepad>() else {
+                continue;
+            };
+            if !gamepad.connected() {
+                continue;
+            }
+            let buttons = gamepad.buttons();
+            for (index, action) in GAMEPAD_BUTTONS {
+                if let Ok(button) = buttons.get(index).dyn_into::<web_sys::GamepadButton>() {
+                    actions.set_value(action, button.value() as f32);
+                }
+            }
+            let stick = gamepad.axes().get(0).as_f64().unwrap_or_default();
+            if stick.abs() > GAMEPAD_DEAD_ZONE {
+                steering = true;
+                let value = ((stick.abs() - GAMEPAD_DEAD_ZONE) / (1.0 - GAMEPAD_DEAD_ZONE)) as f32;
+                if stick < 0.0 {
+                    actions.set_value(Action::SteerLeft, value);
+                } else {
+                    actions.set_value(Action::SteerRight, value);
+                }
+            }
+        }
+        if self.steering && !steering {
+            actions.set_active(Action::Straighten);
+        }
+        self.steering = steering;
+    }
+}
+
+type SharedTouches = Rc<RefCell<Option<Vec<Point>>>>;
+
+///
+/// Touch input on the canvas, mapped to actions by the on-screen `TouchControls`.
+///
+/// The controls are only drawn once the player has touched the screen.
+pub struct Touchscreen {
+    touches: SharedTouches,
+    controls: TouchControls,
+}
+
+impl Touchscreen {
+    pub fn new() -> Result<Self> {
+        Ok(Touchscreen {
+            touches: prepare_touch_input()?,
+            controls: TouchControls::default(),
+        })
+    }
+}
+
+impl Input for Touchscreen {
+    fn process_input(&mut self, actions: &mut ActionState) {
+        if let Some(touches) = self.touches.borrow().as_ref() {
+            self.controls.apply(touches, actions);
+        }
+    }
+    fn draw(&self, renderer: &dyn Renderer, theme: &Theme) {
+        if self.touches.borrow().is_some() {
+            self.controls.draw(renderer, theme);
+        }
+    }
+}
+
+// For Touch Input. Every touch event replaces the list of fingers on the canvas.
+fn prepare_touch_input() -> Result<SharedTouches> {
+    let touches: SharedTouches = Rc::new(RefCell::new(None));
+    let canvas = browser::canvas()?;
+    let handler_touches = Rc::clone(&touches);
+    let handler_canvas = canvas.clone();
+    let ontouch = browser::closure_wrap(Box::new(move |event: web_sys::TouchEvent| {
+        event.prevent_default();
+        let rect = handler_canvas.get_bounding_client_rect();
+        let list = event.touches();
+        let points = (0..list.length())
+            .filter_map(|index| list.item(index))
+            .map(|touch| {
+                // Client pixels inside the border to game coordinates, with the y axis pointing up
+                let x =
+                    (touch.client_x() as f64 - rect.left() - handler_canvas.client_left() as f64)
+                        / handler_canvas.client_width() as f64;
+                let y = (touch.client_y() as f64 - rect.top() - handler_canvas.client_top() as f64)
+                    / handler_canvas.client_height() as f64;
+                Point::new(
+                    x as f32 * CANVAS_WIDTH,
+                    CANVAS_HEIGHT - y as f32 * CANVAS_HEIGHT,
+                )
+            })
+            .collect();
+        *handler_touches.borrow_mut() = Some(points);
+    }) as Box<dyn FnMut(web_sys::TouchEvent)>);
+
+    canvas.set_ontouchstart(Some(ontouch.as_ref().unchecked_ref()));
+    canvas.set_ontouchmove(Some(ontouch.as_ref().unchecked_ref()));
+    canvas.set_ontouchend(Some(ontouch.as_ref().unchecked_ref()));
+    canvas.set_ontouchcancel(Some(ontouch.as_ref().unchecked_ref()));
+    ontouch.forget();
+
+    Ok(touches)
+}
+
+/// Pauses the game whenever the page gets hidden, e.g. the tab is switched,
+/// and lets its sound play again when the page is shown. This runs from the
+/// event itself, as browsers stop animation frames on hidden pages.
+fn prepare_visibility(game: SharedGame) -> Result<()> {
+    let document = browser::document()?;
+    let handler_document = document.clone();
+    let onvisibilitychange = browser::closure_wrap(Box::new(move || {
+        let Ok(mut game) = game.try_borrow_mut() else {
+            return;
+        };
+        match game.as_mut() {
+            Some(game) if handler_document.hidden() => game.pause(),
+            Some(game) => game.show(),
+            None => {}
+        }
+    }) as Box<dyn FnMut()>);
+
+    document.set_onvisibilitychange(Some(onvisibilitychange.as_ref().unchecked_ref()));
+    onvisibilitychange.forget();
+
+    Ok(())
+}
+
+// For Keypress Input
+fn prepare_input() -> Result<UnboundedReceiver<KeyPress>> {
+    let (keydown_sender, keyevent_receiver) = unbounded();
+    let keydown_sender = Rc::new(RefCell::new(keydown_sender));
+    let keyup_sender = Rc::clone(&keydown_sender);
+    let onkeydown = browser::closure_wrap(Box::new(move |keycode: web_sys::KeyboardEvent| {
+        let _ = keydown_sender
+            .borrow_mut()
+            .start_send(KeyPress::KeyDown(keycode));
+    }) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
+
+    let onkeyup = browser::closure_wrap(Box::new(move |keycode: web_sys::KeyboardEvent| {
+        let _ = keyup_sender
+            .borrow_mut()
+            .start_send(KeyPress::KeyUp(keycode));
+    }) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
+
+    browser::canvas()?.set_onkeydown(Some(onkeydown.as_ref().unchecked_ref()));
+    browser::canvas()?.set_onkeyup(Some(onkeyup.as_ref().unchecked_ref()));
+    onkeydown.forget();
+    onkeyup.forget();
+
+    Ok(keyevent_receiver)
+}
+
+/// Local storage key holding the player's mixer volumes
+const MIXER_ITEM: &str = "cart.mixer";
+
+/// Loads the player's mixer volumes, falling back to full volume
+fn load_mixer_settings() -> MixerSettings {
+    match browser::load_item(MIXER_ITEM) {
+        Ok(Some(text)) => MixerSettings::decode(&text).unwrap_or_else(|err| {
+            log!("Error loading mixer settings {:#?}", err);
+            MixerSettings::default()
+        }),
+        _ => MixerSettings::default(),
+    }
+}
+
+/// Web Audio backend. Decoded buffers are kept here and looked up by `Sound::id`.
+///
+/// Browsers keep a new audio context suspended until the player interacts
+/// with the page. Until then looping sounds are queued and one-shots are
+/// dropped, as they would be late by the time they play. Key presses, taps and
+/// clicks resume the context, and the queued sounds start once it runs.
+#[derive(Clone)]
+pub struct WebAudio {
+    context: AudioContext,
+    buffers: Rc<RefCell<Vec<AudioBuffer>>>,
+    mixer: sound::Mixer,
+    settings: Rc<Cell<MixerSettings>>,
+    /// Whether the context has run since the page loaded, so sounds play unqueued
+    unlocked: Rc<Cell<bool>>,
+    /// Whether the game paused every sound with `suspend`
+    paused: Rc<Cell<bool>>,
+    /// Looping sounds played while the context was locked, to start once it unlocks
+    queue: Rc<RefCell<Vec<Rc<QueuedVoice>>>>,
+}
+
+impl WebAudio {
+    pub fn new() -> Result<Self> {
+        let context = sound::create_audio_context()?;
+        let mixer = sound::Mixer::new(&context)?;
+        let settings = load_mixer_settings();
+        mixer.apply(&settings);
+        let audio = WebAudio {
+            unlocked: Rc::new(Cell::new(sound::is_running(&context))),
+            context,
+            buffers: Rc::new(RefCell::new(vec![])),
+            mixer,
+            settings: Rc::new(Cell::new(settings)),
+            paused: Rc::new(Cell::new(false)),
+            queue: Rc::new(RefCell::new(vec![])),
+        };
+        if !audio.unlocked.get() {
+            audio.unlock_on_gesture()?;
+        }
+        Ok(audio)
+    }
+
+    fn buffer(&self, sound: &Sound) -> Result<AudioBuffer> {
+        self.buffers
+            .borrow()
+            .get(sound.id)
+            .cloned()
+            .ok_or_else(|| anyhow!("No sound loaded with id {}", sound.id))
+    }
+
+    fn play(&self, sound: &Sound, looping: bool) -> Result<Box<dyn Playback>> {
+        if !self.unlocked.get() {
+            let voice = Rc::new(QueuedVoice::new(*sound, looping));
+            if looping {
+                self.queue.borrow_mut().push(voice.clone());
+            } else {
+                voice.waiting.set(false);
+            }
+            return Ok(Box::new(voice));
+        }
+        Ok(Box::new(sound::play_sound(
+            &self.context,
+            &self.buffer(sound)?,
+            self.mixer.bus(sound.bus),
+            if looping {
+                sound::Looping::Yes
+            } else {
+                sound::Looping::No
+            },
+        )?))
+    }
+
+    /// Resumes the context on each key press, tap or click anywhere on the page
+    /// until it runs. Browsers only let some gestures start sound, and on phones
+    /// the end of a touch counts but its start does not, so a gesture that is
+    /// refused leaves the sounds queued for the next one.
+    fn unlock_on_gesture(&self) -> Result<()> {
+        let audio = self.clone();
+        let ongesture = browser::closure_wrap(Box::new(move || {
+            if !audio.unlocked.get()
+                && !audio.paused.get()
+                && let Err(err) = sound::resume(&audio.context)
+            {
+                log!("Could not unlock audio {:#?}", err);
+            }
+        }) as Box<dyn FnMut()>);
+        let window = browser::window()?;
+        for event in ["keydown", "touchend", "pointerup", "click"] {
+            window
+                .add_event_listener_with_callback(event, ongesture.as_ref().unchecked_ref())
+                .map_err(|err| anyhow!("Could not listen for {} {:#?}", event, err))?;
+        }
+        ongesture.forget();
+
+        let audio = self.clone();
+        let onstatechange = browser::closure_wrap(Box::new(move || {
+            if !audio.unlocked.get() && sound::is_running(&audio.context) {
+                audio.unlock();
+            }
+        }) as Box<dyn FnMut()>);
+        self.context
+            .set_onstatechange(Some(onstatechange.as_ref().unchecked_ref()));
+        onstatechange.forget();
+        Ok(())
+    }
+
+    /// Starts the queued sounds once the browser lets the context run
+    fn unlock(&self) {
+        self.unlocked.set(true);
+        for voice in self.queue.take() {
+            // Made a one-shot or stopped while it waited
+            if !voice.looping.get() || !voice.is_playing() {
+                continue;
+            }
+            match self.play(&voice.sound, voice.looping.get()) {
+                Ok(playback) => voice.start(playback),
+                Err(err) => {
+                    log!("Error playing queued sound {:#?}", err);
+                }
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Audio for WebAudio {
+    async fn load_sound(&self, filename: &str, bus: Bus) -> Result<Sound> {
+        let array_buffer = browser::fetch_array_buffer(filename).await?;
+
+        let audio_buffer = sound::decode_audio_data(&self.context, &array_buffer).await?;
+
+        let mut buffers = self.buffers.borrow_mut();
+        buffers.push(audio_buffer);
+        Ok(Sound {
+            id: buffers.len() - 1,
+            bus,
+        })
+    }
+
+    fn play_sound(&self, sound: &Sound) -> Result<Box<dyn Playback>> {
+        self.play(sound, false)
+    }
+
+    fn play_looping_sound(&self, sound: &Sound) -> Result<Box<dyn Playback>> {
+        self.play(sound, true)
+    }
+
+    fn play_engine_sound(&self) -> Result<Box<dyn EngineSound>> {
+        Ok(Box::new(sound::play_engine(
+            &self.context,
+            self.mixer.bus(Bus::Effects),
+        )?))
+    }
+
+    fn suspend(&self) -> Result<()> {
+        self.paused.set(true);
+        sound::suspend(&self.context)
+    }
+
+    fn resume(&self) -> Result<()> {
+        self.paused.set(false);
+        // A locked context waits for a gesture and unlocks once it runs
+        sound::resume(&self.context)
+    }
+
+    fn mixer(&self) -> MixerSettings {
+        self.settings.get()
+    }
+
+    fn set_mixer(&self, settings: MixerSettings) -> Result<()> {
+        self.mixer.apply(&settings);
+        self.settings.set(settings);
+        browser::save_item(MIXER_ITEM, &settings.encode())
+    }
+}
+
+impl Playback for sound::Voice {
+    fn stop(&self) -> Result<()> {
+        sound::Voice::stop(self)
+    }
+
+    fn fade_out(&self, seconds: f64) -> Result<()> {
+        sound::Voice::fade_out(self, seconds)
+    }
+
+    fn set_playback_rate(&self, rate: f32) -> Result<()> {
+        sound::Voice::set_playback_rate(self, rate);
+        Ok(())
+    }
+
+    fn set_looping(&self, looping: bool) -> Result<()> {
+        sound::Voice::set_looping(self, looping);
+        Ok(())
+    }
+
+    fn set_pan(&self, pan: f32) -> Result<()> {
+        sound::Voice::set_pan(self, pan);
+        Ok(())
+    }
+
+    fn is_playing(&self) -> bool {
+        sound::Voice::is_playing(self)
+    }
+}
+
+/// A sound played before the context was unlocked. Changes made to a queued
+/// sound while it waits are applied when it starts, and a dropped one never starts.
+struct QueuedVoice {
+    sound: Sound,
+    voice: RefCell<Option<Box<dyn Playback>>>,
+    /// Cleared when the sound is stopped before it starts
+    waiting: Cell<bool>,
+    rate: Cell<f32>,
+    looping: Cell<bool>,
+    pan: Cell<f32>,
+}
+
+impl QueuedVoice {
+    fn new(sound: Sound, looping: bool) -> Self {
+        QueuedVoice {
+            sound,
+            voice: RefCell::new(None),
+            waiting: Cell::new(true),
+            rate: Cell::new(1.0),
+            looping: Cell::new(looping),
+            pan: Cell::new(0.0),
+        }
+    }
+
+    fn start(&self, voice: Box<dyn Playback>) {
+        if let Err(err) = voice
+            .set_playback_rate(self.rate.get())
+            .and_then(|_| voice.set_pan(self.pan.get()))
+        {
+            log!("Error playing queued sound {:#?}", err);
+        }
+        *self.voice.borrow_mut() = Some(voice);
+    }
+}
+
+impl Playback for Rc<QueuedVoice> {
+    fn stop(&self) -> Result<()> {
+        self.waiting.set(false);
+        match self.voice.borrow().as_ref() {
+            Some(voice) => voice.stop(),
+            None => Ok(()),
+        }
+    }
+
+    fn fade_out(&self, seconds: f64) -> Result<()> {
+        self.waiting.set(false);
+        match self.voice.borrow().as_ref() {
+            Some(voice) => voice.fade_out(seconds),
+            None => Ok(()),
+        }
+    }
+
+    fn set_playback_rate(&self, rate: f32) -> Result<()> {
+        self.rate.set(rate);
+        match self.voice.borrow().as_ref() {
+            Some(voice) => voice.set_playback_rate(rate),
+            None => Ok(()),
+        }
+    }
+
+    fn set_looping(&self, looping: bool) -> Result<()> {
+        self.looping.set(looping);
+        match self.voice.borrow().as_ref() {
+            Some(voice) => voice.set_looping(looping),
+            None => Ok(()),
+        }
+    }
+
+    fn set_pan(&self, pan: f32) -> Result<()> {
+        self.pan.set(pan);
+        match self.voice.borrow().as_ref() {
+            Some(voice) => voice.set_pan(pan),
+            None => Ok(()),
+        }
+    }
+
+    fn is_playing(&self) -> bool {
+        match self.voice.borrow().as_ref() {
+            Some(voice) => voice.is_playing(),
+            None => self.waiting.get(),
+        }
+    }
+}
+
+impl EngineSound for sound::EngineDrone {
+    fn set_speed(&self, speed: f32) -> Result<()> {
+        sound::EngineDrone::set_speed(self, speed)
+    }
+
+    fn fade_out(&self, seconds: f64) -> Result<()> {
+        sound::EngineDrone::fade_out(self, seconds)
+    }
+}
+
+/// Query parameter overriding the URL the assets are loaded from
+const ASSET_BASE_PARAM: &str = "base";
+
+/// Loads fonts, sprites and tracks over the network, relative to a base URL
+pub struct WebAssets {
+    base: String,
+}
+
+impl WebAssets {
+    ///
+    /// Takes the asset base URL from the `?base=<url>` query parameter, or
+    /// else from the page, so that the game works under any path.
+    pub fn new() -> Result<Self> {
+        let page = browser::base_uri()?;
+        let base = match browser::query_param(ASSET_BASE_PARAM)? {
+            // Without the slash the last path segment would be replaced
+            Some(base) if !base.ends_with('/') => format!("{}/", base),
+            Some(base) => base,
+            None => page.clone(),
+        };
+        Ok(WebAssets {
+            base: browser::resolve_url(&base, &page)?,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl AssetLoader for WebAssets {
+    fn locate(&self, path: &str) -> String {
+        browser::resolve_url(path, &self.base).unwrap_or_else(|err| {
+            log!("Error locating {} {:#?}", path, err);
+            path.to_string()
+        })
+    }
+
+    async fn load_font(&self, family: &str, url: &str) -> Result<()> {
+        browser::load_font(family, url).await
+    }
+
+    async fn load_text(&self, url: &str) -> Result<String> {
+        browser::fetch_text(url).await
+    }
+}